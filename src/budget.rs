@@ -0,0 +1,119 @@
+use core::{fmt, mem};
+
+use crate::graph_diff::{GraphDiff, RemoveEdge};
+
+/// What a [`crate::History`] does with its oldest entries once it goes over
+/// its [`HistoryBudget`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetentionPolicy {
+    /// Forget them, so they can no longer be undone.
+    DropOldest,
+    /// Merge them into a single entry with [`crate::coalesce`], so the oldest
+    /// state can still be undone to in one step. This only saves space when
+    /// the entries overlap (say, repeated updates to the same vertex). If the
+    /// merged entry is still too big, it's dropped.
+    Coalesce,
+}
+
+/// Limits on how much a [`crate::History`] keeps around. Entries that can be
+/// redone are never dropped, so the limits only apply to the undo side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryBudget {
+    pub max_entries: Option<usize>,
+    /// As estimated by the history's [`SizeEstimator`].
+    pub max_bytes: Option<usize>,
+    pub policy: RetentionPolicy,
+}
+
+/// How much memory a [`crate::History`] is using.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HistoryStats {
+    pub undo_entries: usize,
+    pub redo_entries: usize,
+    /// Estimated size of all entries, in bytes.
+    pub bytes: usize,
+    /// Entries dropped to stay within budget so far. Their changes can no
+    /// longer be undone.
+    pub dropped_entries: usize,
+    /// Entries merged into older ones to stay within budget so far. Their
+    /// changes can still be undone, just not one at a time.
+    pub coalesced_entries: usize,
+}
+
+/// Estimates how many bytes vertex and edge data takes up, including anything
+/// it owns on the heap. By default only `size_of` is counted. Estimates below
+/// `size_of` are treated as `size_of`.
+///
+/// A diff's size is `size_of` the diff, plus whatever it owns on the heap:
+/// the part of each estimate past `size_of` (the rest is already stored
+/// inline), and the buffers holding a removed vertex's edges and the diffs
+/// of a group.
+pub struct SizeEstimator<V, E> {
+    pub vertex: fn(&V) -> usize,
+    pub edge: fn(&E) -> usize,
+}
+
+impl<V, E> SizeEstimator<V, E> {
+    pub(crate) fn diff_size<VP, EP>(&self, diff: &GraphDiff<V, E, VP, EP>) -> usize {
+        let heap_size = match diff {
+            GraphDiff::AddVertex(diff) => self.vertex_heap_size(&diff.vertex_data),
+            GraphDiff::AddEdge(diff) => self.edge_heap_size(&diff.edge_data),
+            GraphDiff::RemoveEdge(diff) => self.edge_heap_size(&diff.edge.data),
+            // its edges are removed first, so it has no connections left
+            GraphDiff::RemoveVertex(diff) => {
+                self.vertex_heap_size(&diff.vertex.data)
+                    + diff
+                        .removed_edges
+                        .iter()
+                        .map(|removed_edge| {
+                            mem::size_of::<RemoveEdge<E>>()
+                                + self.edge_heap_size(&removed_edge.edge.data)
+                        })
+                        .sum::<usize>()
+            }
+            GraphDiff::UpdateVertexData(diff) => {
+                self.vertex_heap_size(&diff.before) + self.vertex_heap_size(&diff.after)
+            }
+            GraphDiff::UpdateEdgeData(diff) => {
+                self.edge_heap_size(&diff.before) + self.edge_heap_size(&diff.after)
+            }
+            // the patch itself is stored inline
+            GraphDiff::PatchVertexData(_) | GraphDiff::PatchEdgeData(_) => 0,
+            // each diff's inline part is in the group's buffer
+            GraphDiff::Group(diffs) => diffs.iter().map(|diff| self.diff_size(diff)).sum(),
+        };
+
+        mem::size_of::<GraphDiff<V, E, VP, EP>>() + heap_size
+    }
+
+    fn vertex_heap_size(&self, data: &V) -> usize {
+        (self.vertex)(data).saturating_sub(mem::size_of::<V>())
+    }
+
+    fn edge_heap_size(&self, data: &E) -> usize {
+        (self.edge)(data).saturating_sub(mem::size_of::<E>())
+    }
+}
+
+impl<V, E> Default for SizeEstimator<V, E> {
+    fn default() -> Self {
+        SizeEstimator {
+            vertex: |_| mem::size_of::<V>(),
+            edge: |_| mem::size_of::<E>(),
+        }
+    }
+}
+
+impl<V, E> Clone for SizeEstimator<V, E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<V, E> Copy for SizeEstimator<V, E> {}
+
+impl<V, E> fmt::Debug for SizeEstimator<V, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SizeEstimator").finish_non_exhaustive()
+    }
+}
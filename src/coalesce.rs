@@ -0,0 +1,228 @@
+//! Merging runs of small diffs (e.g. every mouse move while dragging a vertex)
+//! into fewer, larger ones.
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    graph::{EdgeIndex, VertexIndex},
+    graph_diff::GraphDiff,
+    validate::SlotKind,
+};
+
+/// Shrink a list of diffs without changing what applying or rolling it back
+/// does to the data in the graph.
+///
+/// - Groups are flattened into the list.
+/// - An update is merged into the previous update of the same index, keeping
+///   the earliest `before` and the latest `after`, as long as nothing in
+///   between touches that vertex or edge.
+/// - A vertex or edge that is added and later removed is dropped entirely,
+///   along with everything done to it in between. Applying the result doesn't
+///   bump the generation of that slot, so this only happens when no other diff
+///   in the list refers to the slot.
+pub fn coalesce<V, E, VP, EP>(diffs: Vec<GraphDiff<V, E, VP, EP>>) -> Vec<GraphDiff<V, E, VP, EP>> {
+    coalesce_with(diffs, |_, _| false)
+}
+
+/// Same as [`coalesce`], but adds and removes on slots that are `reserved`
+/// (because something outside of `diffs` refers to them) are always kept.
+pub(crate) fn coalesce_with<V, E, VP, EP, F>(
+    diffs: Vec<GraphDiff<V, E, VP, EP>>,
+    reserved: F,
+) -> Vec<GraphDiff<V, E, VP, EP>>
+where
+    F: Fn(SlotKind, usize) -> bool,
+{
+    let mut flat = Vec::new();
+    flatten(diffs, &mut flat);
+
+    let mut diffs: Vec<Option<GraphDiff<V, E, VP, EP>>> = flat.into_iter().map(Some).collect();
+
+    // edges first, so edges that come and go while their vertex exists don't
+    // keep the vertex from being cancelled
+    cancel_edges(&mut diffs, &reserved);
+    cancel_verticies(&mut diffs, &reserved);
+
+    merge_updates(diffs.into_iter().flatten())
+}
+
+fn flatten<V, E, VP, EP>(
+    diffs: Vec<GraphDiff<V, E, VP, EP>>,
+    flat: &mut Vec<GraphDiff<V, E, VP, EP>>,
+) {
+    for diff in diffs {
+        match diff {
+            GraphDiff::Group(diffs) => flatten(diffs, flat),
+            diff => flat.push(diff),
+        }
+    }
+}
+
+fn cancel_edges<V, E, VP, EP, F>(diffs: &mut [Option<GraphDiff<V, E, VP, EP>>], reserved: &F)
+where
+    F: Fn(SlotKind, usize) -> bool,
+{
+    for removed_at in 0..diffs.len() {
+        let index = match &diffs[removed_at] {
+            Some(GraphDiff::RemoveEdge(diff)) => diff.edge_index,
+            _ => continue,
+        };
+
+        let added_at = match find_add_edge(&diffs[..removed_at], index) {
+            Some(added_at) => added_at,
+            None => continue,
+        };
+
+        let mut dropped = vec![added_at, removed_at];
+        dropped.extend(find_edge_updates(diffs, added_at..removed_at, &[index]));
+
+        drop_if_isolated(
+            diffs,
+            &dropped,
+            &[(SlotKind::Edge, index.0.index)],
+            reserved,
+        );
+    }
+}
+
+fn cancel_verticies<V, E, VP, EP, F>(diffs: &mut [Option<GraphDiff<V, E, VP, EP>>], reserved: &F)
+where
+    F: Fn(SlotKind, usize) -> bool,
+{
+    for removed_at in 0..diffs.len() {
+        let (index, edges): (VertexIndex, Vec<EdgeIndex>) = match &diffs[removed_at] {
+            Some(GraphDiff::RemoveVertex(diff)) => (
+                diff.vertex_index,
+                diff.removed_edges
+                    .iter()
+                    .map(|removed_edge| removed_edge.edge_index)
+                    .collect(),
+            ),
+            _ => continue,
+        };
+
+        let added_at = match diffs[..removed_at].iter().rposition(
+            |diff| matches!(diff, Some(GraphDiff::AddVertex(diff)) if diff.vertex_index == index),
+        ) {
+            Some(added_at) => added_at,
+            None => continue,
+        };
+
+        let mut dropped = vec![added_at, removed_at];
+
+        // the edges removed along with the vertex have to go too
+        for edge in &edges {
+            match find_add_edge(&diffs[added_at..removed_at], *edge) {
+                Some(edge_added_at) => dropped.push(added_at + edge_added_at),
+                None => break,
+            }
+        }
+        if dropped.len() != edges.len() + 2 {
+            continue;
+        }
+
+        dropped.extend(find_edge_updates(diffs, added_at..removed_at, &edges));
+        dropped.extend((added_at..removed_at).filter(|position| {
+            matches!(&diffs[*position], Some(GraphDiff::UpdateVertexData(diff)) if diff.index == index)
+        }));
+
+        let mut slots = vec![(SlotKind::Vertex, index.0.index)];
+        slots.extend(edges.iter().map(|edge| (SlotKind::Edge, edge.0.index)));
+
+        drop_if_isolated(diffs, &dropped, &slots, reserved);
+    }
+}
+
+fn find_add_edge<V, E, VP, EP>(
+    diffs: &[Option<GraphDiff<V, E, VP, EP>>],
+    index: EdgeIndex,
+) -> Option<usize> {
+    diffs.iter().rposition(
+        |diff| matches!(diff, Some(GraphDiff::AddEdge(diff)) if diff.edge_index == index),
+    )
+}
+
+fn find_edge_updates<'a, V, E, VP, EP>(
+    diffs: &'a [Option<GraphDiff<V, E, VP, EP>>],
+    range: core::ops::Range<usize>,
+    edges: &'a [EdgeIndex],
+) -> impl Iterator<Item = usize> + 'a {
+    range.filter(move |position| {
+        matches!(&diffs[*position], Some(GraphDiff::UpdateEdgeData(diff)) if edges.contains(&diff.index))
+    })
+}
+
+/// Drop the diffs at `dropped`, but only if none of `slots` are reserved, and
+/// no diff that's kept touches any of them.
+fn drop_if_isolated<V, E, VP, EP, F>(
+    diffs: &mut [Option<GraphDiff<V, E, VP, EP>>],
+    dropped: &[usize],
+    slots: &[(SlotKind, usize)],
+    reserved: &F,
+) where
+    F: Fn(SlotKind, usize) -> bool,
+{
+    if slots.iter().any(|(kind, slot)| reserved(*kind, *slot)) {
+        return;
+    }
+
+    let isolated = diffs.iter().enumerate().all(|(position, diff)| match diff {
+        Some(diff) if !dropped.contains(&position) => slots
+            .iter()
+            .all(|(kind, slot)| !diff.touches_slot(*kind, *slot)),
+        _ => true,
+    });
+
+    if isolated {
+        for position in dropped {
+            diffs[*position] = None;
+        }
+    }
+}
+
+fn merge_updates<V, E, VP, EP>(
+    diffs: impl Iterator<Item = GraphDiff<V, E, VP, EP>>,
+) -> Vec<GraphDiff<V, E, VP, EP>> {
+    let mut merged: Vec<GraphDiff<V, E, VP, EP>> = Vec::new();
+
+    for diff in diffs {
+        let diff = match diff {
+            GraphDiff::UpdateVertexData(update) => {
+                match last_touching(&mut merged, SlotKind::Vertex, update.index.0.index) {
+                    Some(GraphDiff::UpdateVertexData(previous))
+                        if previous.index == update.index =>
+                    {
+                        previous.after = update.after;
+                        continue;
+                    }
+                    _ => GraphDiff::UpdateVertexData(update),
+                }
+            }
+            GraphDiff::UpdateEdgeData(update) => {
+                match last_touching(&mut merged, SlotKind::Edge, update.index.0.index) {
+                    Some(GraphDiff::UpdateEdgeData(previous)) if previous.index == update.index => {
+                        previous.after = update.after;
+                        continue;
+                    }
+                    _ => GraphDiff::UpdateEdgeData(update),
+                }
+            }
+            diff => diff,
+        };
+
+        merged.push(diff);
+    }
+
+    merged
+}
+
+fn last_touching<V, E, VP, EP>(
+    diffs: &mut [GraphDiff<V, E, VP, EP>],
+    kind: SlotKind,
+    slot: usize,
+) -> Option<&mut GraphDiff<V, E, VP, EP>> {
+    diffs
+        .iter_mut()
+        .rev()
+        .find(|diff| diff.touches_slot(kind, slot))
+}
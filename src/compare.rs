@@ -0,0 +1,112 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use crate::{
+    gen_vec::Index,
+    graph::{Edge, EdgeIndex, Graph, VertexIndex},
+    graph_diff::{AddEdge, AddVertex, GraphDiff},
+};
+
+impl<V: Clone + PartialEq, E: Clone + PartialEq> Graph<V, E> {
+    /// The diffs that turn this graph into `other`, found by comparing the two
+    /// slot by slot. A vertex or edge is the same in both if it has the same
+    /// index (including generation), in which case only its data is compared.
+    /// Anything else is removed and added again.
+    ///
+    /// Adding can only reuse a slot at the generation it's currently open at,
+    /// so something that's at a different generation in `other` ends up in
+    /// the same slot, but not at the same index. The order slots are handed out
+    /// in afterwards may also differ, as free lists aren't compared.
+    pub fn diff_against(&self, other: &Graph<V, E>) -> Vec<GraphDiff<V, E>> {
+        // each diff is applied as it's made, so later ones see its effects
+        let mut graph = self.clone();
+        let mut diffs = Vec::new();
+
+        let edge_kept = |index: EdgeIndex, edge: &Edge<E>| {
+            matches!(other.get_edge(index), Some(other_edge)
+                if other_edge.from == edge.from && other_edge.to == edge.to)
+        };
+
+        // removing edges first means removing verticies won't take any with it
+        for (index, edge) in self.edge_iter() {
+            if !edge_kept(index, edge) {
+                diffs.push(graph.remove_edge(index).unwrap());
+            }
+        }
+
+        for index in self.vertex_indexes() {
+            if other.get_vertex(index).is_none() {
+                diffs.push(graph.remove_vertex(index).unwrap());
+            }
+        }
+
+        let mut vertex_map = BTreeMap::new();
+        for (index, vertex) in other.vertex_iter() {
+            if self.get_vertex(index).is_some() {
+                if self[index].data != vertex.data {
+                    let (_, diff) = graph.update_vertex(index, vertex.data.clone()).unwrap();
+                    diffs.push(diff);
+                }
+
+                continue;
+            }
+
+            let generation = graph.verticies.open_generation(index.0.index).unwrap();
+            let new_index = VertexIndex(Index {
+                index: index.0.index,
+                generation,
+            });
+            vertex_map.insert(index, new_index);
+
+            diffs.push(apply(
+                &mut graph,
+                GraphDiff::AddVertex(AddVertex {
+                    vertex_index: new_index,
+                    vertex_data: vertex.data.clone(),
+                    open_generation: None,
+                }),
+            ));
+        }
+
+        let map_vertex = |index: VertexIndex| vertex_map.get(&index).copied().unwrap_or(index);
+
+        for (index, edge) in other.edge_iter() {
+            if self
+                .get_edge(index)
+                .is_some_and(|own| edge_kept(index, own))
+            {
+                if self[index].data != edge.data {
+                    let (_, diff) = graph.update_edge(index, edge.data.clone()).unwrap();
+                    diffs.push(diff);
+                }
+
+                continue;
+            }
+
+            let generation = graph.edges.open_generation(index.0.index).unwrap();
+
+            diffs.push(apply(
+                &mut graph,
+                GraphDiff::AddEdge(AddEdge {
+                    edge_index: EdgeIndex(Index {
+                        index: index.0.index,
+                        generation,
+                    }),
+                    from: map_vertex(edge.from),
+                    to: map_vertex(edge.to),
+                    edge_data: edge.data.clone(),
+                    open_generation: None,
+                }),
+            ));
+        }
+
+        diffs
+    }
+}
+
+fn apply<V: Clone, E: Clone>(graph: &mut Graph<V, E>, diff: GraphDiff<V, E>) -> GraphDiff<V, E> {
+    graph
+        .apply_diff_ref(&diff)
+        .expect("Diffing graphs produced an invalid diff");
+
+    diff
+}
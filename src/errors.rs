@@ -1,6 +1,13 @@
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::fmt::Debug;
+
 use snafu::Snafu;
 
-use crate::graph::{EdgeIndex, VertexIndex};
+use crate::{
+    graph::{EdgeIndex, GraphIndex, VertexIndex},
+    replicated::{EdgeId, VertexId},
+    undo_tree::StateIndex,
+};
 
 #[derive(Snafu, Debug)]
 #[snafu(visibility(pub))]
@@ -9,6 +16,91 @@ pub enum GraphError {
     VertexDoesNotExist { index: VertexIndex },
     #[snafu(display("Edge `{index:?}` does not exist"))]
     EdgeDoesNotExist { index: EdgeIndex },
-    #[snafu(display("Invalid diff"))]
-    InvalidDiff,
+    #[snafu(display("Edge `{edge:?}` refers to vertex `{vertex:?}`, which does not exist"))]
+    DanglingEndpoint {
+        edge: EdgeIndex,
+        vertex: VertexIndex,
+    },
+    #[snafu(display("Vertex slot for `{index:?}` is already occupied"))]
+    VertexSlotOccupied { index: VertexIndex },
+    #[snafu(display("Edge slot for `{index:?}` is already occupied"))]
+    EdgeSlotOccupied { index: EdgeIndex },
+    #[snafu(display(
+        "Vertex slot for `{index:?}` is at generation {actual}, expected {expected}"
+    ))]
+    VertexGenerationMismatch {
+        index: VertexIndex,
+        expected: u32,
+        actual: u32,
+    },
+    #[snafu(display("Edge slot for `{index:?}` is at generation {actual}, expected {expected}"))]
+    EdgeGenerationMismatch {
+        index: EdgeIndex,
+        expected: u32,
+        actual: u32,
+    },
+    #[snafu(display(
+        "Edge `{index:?}` connects `{actual_from:?}` to `{actual_to:?}`, expected `{expected_from:?}` to `{expected_to:?}`"
+    ))]
+    EdgeEndpointsMismatch {
+        index: EdgeIndex,
+        expected_from: VertexIndex,
+        expected_to: VertexIndex,
+        actual_from: VertexIndex,
+        actual_to: VertexIndex,
+    },
+    #[snafu(display("Diff {position} in batch failed: {source}"))]
+    DiffBatchFailed {
+        position: usize,
+        source: Box<GraphError>,
+    },
+    #[snafu(display("There is nothing to undo"))]
+    NothingToUndo,
+    #[snafu(display("There is nothing to redo"))]
+    NothingToRedo,
+    #[snafu(display("Undo state `{index:?}` does not exist"))]
+    StateDoesNotExist { index: StateIndex },
+    #[snafu(display(
+        "Undo state `{index:?}` can't be pruned, as the current state depends on it"
+    ))]
+    StateInUse { index: StateIndex },
+    #[snafu(display("Checkpoint `{label}` does not exist"))]
+    CheckpointDoesNotExist { label: String },
+    #[snafu(display("History entry {entry} can't be undone, as it hasn't been applied"))]
+    EntryNotApplied { entry: usize },
+    #[snafu(display(
+        "History entry {entry} can't be undone, as later entries changed `{conflicts:?}`"
+    ))]
+    UndoConflict {
+        entry: usize,
+        conflicts: Vec<GraphIndex>,
+    },
+}
+
+/// Errors from a [`crate::KeyedGraph`], which refer to vertices and edges by
+/// key.
+#[derive(Snafu, Debug)]
+#[snafu(visibility(pub))]
+pub enum KeyedError<K: Debug> {
+    #[snafu(display("Key `{key:?}` does not exist"))]
+    KeyDoesNotExist { key: K },
+    #[snafu(display("Key `{key:?}` is already in use"))]
+    KeyInUse { key: K },
+    #[snafu(display(
+        "Diff adds or removes `{index:?}`, which needs a key; use a keyed diff instead"
+    ))]
+    NotKeyed { index: GraphIndex },
+    #[snafu(context(false), display("{source}"))]
+    Graph { source: GraphError },
+}
+
+/// Errors from a [`crate::ReplicatedGraph`], which refers to vertices and
+/// edges by id.
+#[derive(Snafu, Debug)]
+#[snafu(visibility(pub))]
+pub enum ReplicatedError {
+    #[snafu(display("Vertex `{id:?}` does not exist"))]
+    VertexIdDoesNotExist { id: VertexId },
+    #[snafu(display("Edge `{id:?}` does not exist"))]
+    EdgeIdDoesNotExist { id: EdgeId },
 }
@@ -0,0 +1,185 @@
+//! Serialization that keeps the exact slot layout of a [`GenVec`] or [`Graph`].
+//!
+//! The default serialization of a [`GenVec`] only writes occupied entries, so
+//! after loading, open slots start over at generation 0 and diffs that refer
+//! to removed indices can no longer be rolled back. This mode also writes open
+//! slots (with their generations) and the list of free slots, so history
+//! survives a save/load round trip.
+//!
+//! Use it on a field with `#[serde(with = "ddgg::full_fidelity")]`, or wrap a
+//! value in [`FullFidelity`].
+
+use alloc::vec::Vec;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    gen_vec::{Element, GenVec},
+    graph::{Edge, Graph, Vertex},
+};
+
+/// Wrapper that (de)serializes its contents in full fidelity mode.
+#[derive(Debug, Clone)]
+pub struct FullFidelity<T>(pub T);
+
+/// Types that can be serialized in full fidelity mode.
+pub trait SerializeFull {
+    fn serialize_full<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>;
+}
+
+/// Types that can be deserialized from the full fidelity format.
+pub trait DeserializeFull<'de>: Sized {
+    fn deserialize_full<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>;
+}
+
+pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: SerializeFull,
+    S: Serializer,
+{
+    value.serialize_full(serializer)
+}
+
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: DeserializeFull<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize_full(deserializer)
+}
+
+impl<T: SerializeFull> Serialize for FullFidelity<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize_full(serializer)
+    }
+}
+
+impl<'de, T: DeserializeFull<'de>> Deserialize<'de> for FullFidelity<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize_full(deserializer).map(FullFidelity)
+    }
+}
+
+impl<T: SerializeFull> SerializeFull for &T {
+    fn serialize_full<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize_full(serializer)
+    }
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "js_names", serde(rename_all = "camelCase"))]
+struct GenVecRef<'a, T> {
+    slots: &'a [Element<T>],
+    next_open_slot: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "js_names", serde(rename_all = "camelCase"))]
+struct GenVecOwned<T> {
+    slots: Vec<Element<T>>,
+    next_open_slot: Option<usize>,
+}
+
+impl<T: Serialize> SerializeFull for GenVec<T> {
+    fn serialize_full<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        GenVecRef {
+            slots: &self.vec,
+            next_open_slot: self.next_open_slot,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> DeserializeFull<'de> for GenVec<T> {
+    fn deserialize_full<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let GenVecOwned {
+            slots,
+            next_open_slot,
+        } = GenVecOwned::deserialize(deserializer)?;
+
+        // walk the free list, making sure it only visits open slots, and each
+        // of them exactly once
+        let mut visited = 0;
+        let mut current = next_open_slot;
+
+        while let Some(index) = current {
+            match slots.get(index) {
+                Some(Element::Open { next, .. }) => {
+                    visited += 1;
+
+                    if visited > slots.len() {
+                        return Err(de::Error::custom("free list contains a cycle"));
+                    }
+
+                    current = *next;
+                }
+                Some(Element::Occupied { .. }) => {
+                    return Err(de::Error::custom(format_args!(
+                        "free list points at occupied slot {}",
+                        index
+                    )));
+                }
+                None => {
+                    return Err(de::Error::custom(format_args!(
+                        "free list points at slot {} which is out of bounds",
+                        index
+                    )));
+                }
+            }
+        }
+
+        let open_count = slots
+            .iter()
+            .filter(|slot| matches!(slot, Element::Open { .. }))
+            .count();
+
+        if visited != open_count {
+            return Err(de::Error::custom("not all open slots are in the free list"));
+        }
+
+        let mut gen_vec = GenVec {
+            vec: slots,
+            next_open_slot,
+        };
+        gen_vec.relink_prev();
+
+        Ok(gen_vec)
+    }
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "js_names", serde(rename_all = "camelCase"))]
+struct GraphRef<'a, V: Serialize, E: Serialize> {
+    #[serde(serialize_with = "serialize")]
+    verticies: &'a GenVec<Vertex<V>>,
+    #[serde(serialize_with = "serialize")]
+    edges: &'a GenVec<Edge<E>>,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "js_names", serde(rename_all = "camelCase"))]
+#[serde(bound(deserialize = "V: Deserialize<'de>, E: Deserialize<'de>"))]
+struct GraphOwned<V, E> {
+    #[serde(deserialize_with = "deserialize")]
+    verticies: GenVec<Vertex<V>>,
+    #[serde(deserialize_with = "deserialize")]
+    edges: GenVec<Edge<E>>,
+}
+
+impl<V: Serialize, E: Serialize> SerializeFull for Graph<V, E> {
+    fn serialize_full<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        GraphRef {
+            verticies: &self.verticies,
+            edges: &self.edges,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, V: Deserialize<'de>, E: Deserialize<'de>> DeserializeFull<'de> for Graph<V, E> {
+    fn deserialize_full<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let GraphOwned { verticies, edges } = GraphOwned::deserialize(deserializer)?;
+
+        Graph::from_slots(verticies, edges).map_err(de::Error::custom)
+    }
+}
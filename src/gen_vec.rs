@@ -94,6 +94,10 @@ pub enum Element<T> {
     Open {
         generation: u32,
         next: Option<usize>,
+        /// Previous open slot, so any slot can be unlinked from the free list
+        /// in constant time. Rebuilt from `next` when deserializing.
+        #[cfg_attr(feature = "serde", serde(skip))]
+        prev: Option<usize>,
     },
 }
 
@@ -111,13 +115,17 @@ impl<T> Element<T> {
             Self::Open { generation, .. } => *generation,
         }
     }
+}
 
-    pub fn next_open(&self) -> Option<usize> {
-        match self {
-            Self::Occupied { .. } => None,
-            Self::Open { next, .. } => *next,
-        }
-    }
+/// Where a slot was before [`GenVec::occupy`] took it, so it can be put back
+/// exactly, free list position and all.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Vacancy {
+    generation: u32,
+    /// The open slot before it in the free list
+    prev: Option<usize>,
+    /// Length of the vec before it was grown to fit the slot
+    len: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -138,15 +146,13 @@ impl<T> GenVec<T> {
         // there was an open slot, put it there
         if let Some(open_slot_index) = self.next_open_slot {
             let generation = self.vec[open_slot_index].generation();
-            let next_open = self.vec[open_slot_index].next_open();
 
+            self.unlink_open(open_slot_index);
             self.vec[open_slot_index] = Element::Occupied { value, generation };
 
-            self.next_open_slot = next_open;
-
             Index {
                 index: open_slot_index,
-                generation: generation,
+                generation,
             }
         } else {
             // else, add it to the end
@@ -176,39 +182,108 @@ impl<T> GenVec<T> {
     }
 
     pub fn get_mut(&mut self, index: Index) -> Option<&mut T> {
-        match self.vec[index.index] {
-            Element::Occupied {
-                ref mut value,
-                generation,
-            } => {
-                if generation == index.generation {
+        match self.vec.get_mut(index.index) {
+            Some(Element::Occupied { value, generation }) => {
+                if *generation == index.generation {
                     Some(value)
                 } else {
                     None
                 }
             }
-            Element::Open { .. } => None,
+            _ => None,
         }
     }
 
     pub fn remove(&mut self, index: Index) -> Option<T> {
-        let can_take = self.get(index).is_some();
+        self.remove_with_generation(index, index.generation + 1)
+    }
 
-        if can_take {
-            let removed = mem::replace(
-                &mut self.vec[index.index],
-                Element::Open {
-                    generation: index.generation + 1,
-                    next: self.next_open_slot,
-                },
-            );
+    /// Put `value` into a specific open slot, giving it the generation in
+    /// `index`. Slots past the end are treated as open slots with generation 0,
+    /// and the vec is grown to fit. The slot is unlinked from the free list,
+    /// so it will never be handed out by [`GenVec::add`] while occupied.
+    ///
+    /// Returns the value back if the slot is occupied, or if the generation in
+    /// `index` is older than the open slot's, as that would make indices to
+    /// values already removed from the slot valid again.
+    pub fn occupy(&mut self, index: Index, value: T) -> Result<(), T> {
+        if let Some(Element::Open { generation, .. }) = self.vec.get(index.index) {
+            if index.generation < *generation {
+                return Err(value);
+            }
+        }
 
-            self.next_open_slot = Some(index.index);
+        self.occupy_vacancy(index, value).map(|_| ())
+    }
 
-            Some(removed.as_occupied().expect("to exist"))
-        } else {
-            None
+    /// [`GenVec::occupy`], returning what [`GenVec::vacate`] needs to undo it.
+    /// The generation isn't checked, as rolling back a removal puts the value
+    /// back at the generation it had before.
+    pub(crate) fn occupy_vacancy(&mut self, index: Index, value: T) -> Result<Vacancy, T> {
+        let len = self.vec.len();
+
+        if index.index >= self.vec.len() {
+            while self.vec.len() <= index.index {
+                self.vec.push(Element::Open {
+                    generation: 0,
+                    next: None,
+                    prev: None,
+                });
+                self.link_open(self.vec.len() - 1);
+            }
         }
+
+        let (generation, prev) = match self.vec[index.index] {
+            Element::Open {
+                generation, prev, ..
+            } => (generation, prev),
+            Element::Occupied { .. } => return Err(value),
+        };
+
+        self.unlink_open(index.index);
+        self.vec[index.index] = Element::Occupied {
+            value,
+            generation: index.generation,
+        };
+
+        Ok(Vacancy {
+            generation,
+            prev,
+            len,
+        })
+    }
+
+    /// Undo [`GenVec::occupy_vacancy`], returning the value. Anything done to
+    /// the free list since must have been undone already.
+    pub(crate) fn vacate(&mut self, index: usize, vacancy: Vacancy) -> Option<T> {
+        if let Element::Open { .. } = self.vec.get(index)? {
+            return None;
+        }
+
+        let removed = mem::replace(
+            &mut self.vec[index],
+            Element::Open {
+                generation: vacancy.generation,
+                next: None,
+                prev: None,
+            },
+        );
+
+        match vacancy.prev {
+            Some(prev) => self.link_open_after(index, prev),
+            None => self.link_open(index),
+        }
+
+        // take back any slots added to fit it
+        if vacancy.len < self.vec.len() {
+            for grown in vacancy.len..self.vec.len() {
+                self.unlink_open(grown);
+            }
+
+            self.vec.truncate(vacancy.len);
+        }
+
+        removed.as_occupied()
     }
 
     pub fn len(&self) -> usize {
@@ -239,6 +314,7 @@ impl<T> GenVec<T> {
 
     pub fn clear(&mut self) {
         self.vec.clear();
+        self.next_open_slot = None;
     }
 
     pub fn iter(&self) -> impl Iterator<Item = (Index, &T)> + '_ {
@@ -289,19 +365,25 @@ impl<T> GenVec<T> {
             })
     }
 
-    pub(crate) fn remove_but_maintain_generation(&mut self, index: Index) -> Option<T> {
+    /// Remove the value, leaving the slot open at `new_generation`.
+    pub(crate) fn remove_with_generation(
+        &mut self,
+        index: Index,
+        new_generation: u32,
+    ) -> Option<T> {
         let can_take = self.get(index).is_some();
 
         if can_take {
             let removed = mem::replace(
                 &mut self.vec[index.index],
                 Element::Open {
-                    generation: index.generation,
-                    next: self.next_open_slot,
+                    generation: new_generation,
+                    next: None,
+                    prev: None,
                 },
             );
 
-            self.next_open_slot = Some(index.index);
+            self.link_open(index.index);
 
             Some(removed.as_occupied().expect("to exist"))
         } else {
@@ -309,19 +391,122 @@ impl<T> GenVec<T> {
         }
     }
 
-    pub(crate) fn is_replaceable_by_index_rollback(&self, index: Index) -> bool {
-        if let Element::Open { generation, .. } = self.vec[index.index] {
-            generation == index.generation + 1
-        } else {
-            false
+    /// Generation of a slot that is open, treating slots past the end as open
+    /// with generation 0. Returns `None` if the slot is occupied.
+    pub(crate) fn open_generation(&self, index: usize) -> Option<u32> {
+        match self.vec.get(index) {
+            Some(Element::Open { generation, .. }) => Some(*generation),
+            Some(Element::Occupied { .. }) => None,
+            None => Some(0),
         }
     }
 
-    pub(crate) fn is_replaceable_by_index_apply(&self, index: Index) -> bool {
-        if let Element::Open { generation, .. } = self.vec[index.index] {
-            generation == index.generation
-        } else {
-            false
+    /// Push an open slot onto the front of the free list.
+    fn link_open(&mut self, index: usize) {
+        let old_head = self.next_open_slot;
+
+        if let Element::Open { next, prev, .. } = &mut self.vec[index] {
+            *next = old_head;
+            *prev = None;
+        }
+
+        if let Some(old_head) = old_head {
+            if let Element::Open { prev, .. } = &mut self.vec[old_head] {
+                *prev = Some(index);
+            }
+        }
+
+        self.next_open_slot = Some(index);
+    }
+
+    /// Put an open slot into the free list, right after `prev`.
+    fn link_open_after(&mut self, index: usize, prev: usize) {
+        let next = match &mut self.vec[prev] {
+            Element::Open { next, .. } => next.replace(index),
+            Element::Occupied { .. } => return,
+        };
+
+        if let Element::Open {
+            next: index_next,
+            prev: index_prev,
+            ..
+        } = &mut self.vec[index]
+        {
+            *index_next = next;
+            *index_prev = Some(prev);
+        }
+
+        if let Some(next) = next {
+            if let Element::Open {
+                prev: next_prev, ..
+            } = &mut self.vec[next]
+            {
+                *next_prev = Some(index);
+            }
+        }
+    }
+
+    /// Take an open slot out of the free list, wherever it is.
+    fn unlink_open(&mut self, index: usize) {
+        let (next, prev) = match self.vec[index] {
+            Element::Open { next, prev, .. } => (next, prev),
+            Element::Occupied { .. } => return,
+        };
+
+        match prev {
+            Some(prev) => {
+                if let Element::Open {
+                    next: prev_next, ..
+                } = &mut self.vec[prev]
+                {
+                    *prev_next = next;
+                }
+            }
+            None => self.next_open_slot = next,
+        }
+
+        if let Some(next) = next {
+            if let Element::Open {
+                prev: next_prev, ..
+            } = &mut self.vec[next]
+            {
+                *next_prev = prev;
+            }
+        }
+
+        if let Element::Open { next, prev, .. } = &mut self.vec[index] {
+            *next = None;
+            *prev = None;
+        }
+    }
+
+    /// Throw away the free list and link up every open slot again, in
+    /// descending order.
+    pub(crate) fn rebuild_free_list(&mut self) {
+        self.next_open_slot = None;
+
+        for index in 0..self.vec.len() {
+            if let Element::Open { .. } = self.vec[index] {
+                self.link_open(index);
+            }
+        }
+    }
+
+    /// Recompute the `prev` links of the free list from the `next` links.
+    #[cfg(feature = "serde")]
+    pub(crate) fn relink_prev(&mut self) {
+        let mut prev_index = None;
+        let mut current = self.next_open_slot;
+
+        while let Some(index) = current {
+            match &mut self.vec[index] {
+                Element::Open { next, prev, .. } => {
+                    *prev = prev_index;
+                    prev_index = Some(index);
+                    current = *next;
+                }
+                Element::Occupied { .. } => break,
+            }
         }
     }
 }
@@ -368,6 +553,7 @@ impl<'de, T: Deserialize<'de>> Deserialize<'de> for GenVec<T> {
                         gen_vec_ref.resize_with(key.index + 1, || Element::Open {
                             generation: 0,
                             next: None,
+                            prev: None,
                         });
                     }
 
@@ -378,17 +564,7 @@ impl<'de, T: Deserialize<'de>> Deserialize<'de> for GenVec<T> {
                 }
 
                 // reconstruct linked list of open spots
-                let mut last_open_index: Option<usize> = None;
-
-                for (index, element) in gen_vec_ref.iter_mut().enumerate() {
-                    if let Element::Open { next, .. } = element {
-                        *next = last_open_index;
-
-                        last_open_index = Some(index);
-                    }
-                }
-
-                gen_vec.next_open_slot = last_open_index;
+                gen_vec.rebuild_free_list();
 
                 Ok(gen_vec)
             }
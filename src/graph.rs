@@ -1,6 +1,6 @@
 use core::{fmt::Debug, mem, ops};
 
-use alloc::vec::Vec;
+use alloc::{boxed::Box, vec, vec::Vec};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -8,10 +8,13 @@ use snafu::OptionExt;
 
 use crate::{
     errors::GraphError,
-    gen_vec::{Element, GenVec, Index},
+    gen_vec::{GenVec, Index, Vacancy},
     graph_diff::{
-        AddEdge, AddVertex, GraphDiff, RemoveEdge, RemoveVertex, UpdateEdgeData, UpdateVertexData,
+        self, AddEdge, AddVertex, GraphDiff, PatchEdgeData, PatchVertexData, RemoveEdge,
+        RemoveVertex, UpdateEdgeData, UpdateVertexData,
     },
+    patch::Patch,
+    validate::SlotKind,
     EdgeDoesNotExistSnafu, VertexDoesNotExistSnafu,
 };
 
@@ -22,17 +25,50 @@ pub struct VertexIndex(pub(crate) Index);
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct EdgeIndex(pub(crate) Index);
 
+/// Either kind of index, for things that can refer to both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "js_names", serde(tag = "variant", content = "data"))]
+pub enum GraphIndex {
+    Vertex(VertexIndex),
+    Edge(EdgeIndex),
+}
+
+impl GraphIndex {
+    pub(crate) fn slot(&self) -> (SlotKind, usize) {
+        match self {
+            GraphIndex::Vertex(index) => (SlotKind::Vertex, index.0.index),
+            GraphIndex::Edge(index) => (SlotKind::Edge, index.0.index),
+        }
+    }
+}
+
+impl From<VertexIndex> for GraphIndex {
+    fn from(index: VertexIndex) -> Self {
+        GraphIndex::Vertex(index)
+    }
+}
+
+impl From<EdgeIndex> for GraphIndex {
+    fn from(index: EdgeIndex) -> Self {
+        GraphIndex::Edge(index)
+    }
+}
+
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "js_names", serde(rename_all = "camelCase"))]
 pub struct Vertex<T> {
-    connections_from: Vec<(VertexIndex, EdgeIndex)>,
-    connections_to: Vec<(VertexIndex, EdgeIndex)>,
-    data: T,
+    // connections can be rebuilt from the edges, so they aren't stored
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) connections_from: Vec<(VertexIndex, EdgeIndex)>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) connections_to: Vec<(VertexIndex, EdgeIndex)>,
+    pub(crate) data: T,
 }
 
 impl<T> Vertex<T> {
-    fn new(data: T) -> Vertex<T> {
+    pub(crate) fn new(data: T) -> Vertex<T> {
         Vertex {
             connections_from: Vec::new(),
             connections_to: Vec::new(),
@@ -52,15 +88,34 @@ impl<T> Vertex<T> {
         &self.data
     }
 
-    fn add_from_unchecked(&mut self, from: VertexIndex, edge: EdgeIndex) {
+    /// Every edge connected to this vertex in either direction. Self-loops show
+    /// up in both connection lists, but are only returned once.
+    pub fn get_connected_edges(&self) -> Vec<EdgeIndex> {
+        let mut edges: Vec<EdgeIndex> = self
+            .connections_from
+            .iter()
+            .map(|connection| connection.1)
+            .collect();
+
+        for (_, edge_index) in self.connections_to.iter() {
+            if !edges.contains(edge_index) {
+                edges.push(*edge_index);
+            }
+        }
+
+        edges
+    }
+
+    pub(crate) fn add_from_unchecked(&mut self, from: VertexIndex, edge: EdgeIndex) {
         self.connections_from.push((from, edge));
     }
 
-    fn add_to_unchecked(&mut self, to: VertexIndex, edge: EdgeIndex) {
+    pub(crate) fn add_to_unchecked(&mut self, to: VertexIndex, edge: EdgeIndex) {
         self.connections_to.push((to, edge));
     }
 
-    fn remove_from(&mut self, edge_index: EdgeIndex) -> Result<(), ()> {
+    /// Returns where the connection was.
+    fn remove_from(&mut self, edge_index: EdgeIndex) -> Result<usize, ()> {
         let position = self
             .connections_from
             .iter()
@@ -69,10 +124,11 @@ impl<T> Vertex<T> {
 
         self.connections_from.remove(position);
 
-        Ok(())
+        Ok(position)
     }
 
-    fn remove_to(&mut self, edge_index: EdgeIndex) -> Result<(), ()> {
+    /// Returns where the connection was.
+    fn remove_to(&mut self, edge_index: EdgeIndex) -> Result<usize, ()> {
         let position = self
             .connections_to
             .iter()
@@ -81,7 +137,7 @@ impl<T> Vertex<T> {
 
         self.connections_to.remove(position);
 
-        Ok(())
+        Ok(position)
     }
 }
 
@@ -89,9 +145,9 @@ impl<T> Vertex<T> {
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "js_names", serde(rename_all = "camelCase"))]
 pub struct Edge<T> {
-    from: VertexIndex,
-    to: VertexIndex,
-    data: T,
+    pub(crate) from: VertexIndex,
+    pub(crate) to: VertexIndex,
+    pub(crate) data: T,
 }
 
 impl<T> Edge<T> {
@@ -115,12 +171,59 @@ impl<T> Edge<T> {
 /// Main graph structure
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "GraphSlots<V, E>"))]
 #[cfg_attr(feature = "js_names", serde(rename_all = "camelCase"))]
 pub struct Graph<V, E> {
+    pub(crate) verticies: GenVec<Vertex<V>>,
+    pub(crate) edges: GenVec<Edge<E>>,
+}
+
+/// What a serialized graph holds. Connection lists are rebuilt on load.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[cfg_attr(feature = "js_names", serde(rename_all = "camelCase"))]
+struct GraphSlots<V, E> {
     verticies: GenVec<Vertex<V>>,
     edges: GenVec<Edge<E>>,
 }
 
+#[cfg(feature = "serde")]
+impl<V, E> Graph<V, E> {
+    /// Put a graph back together from its slots, rebuilding the connection
+    /// lists from the edges. Used when deserializing, where the connection
+    /// lists aren't stored.
+    pub(crate) fn from_slots(
+        verticies: GenVec<Vertex<V>>,
+        edges: GenVec<Edge<E>>,
+    ) -> Result<Graph<V, E>, GraphError> {
+        let mut graph = Graph { verticies, edges };
+
+        for (index, edge) in graph.edges.iter() {
+            for vertex in [edge.from, edge.to] {
+                if graph.verticies.get(vertex.0).is_none() {
+                    return Err(GraphError::DanglingEndpoint {
+                        edge: EdgeIndex(index),
+                        vertex,
+                    });
+                }
+            }
+        }
+
+        graph.rebuild_connections();
+
+        Ok(graph)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<V, E> TryFrom<GraphSlots<V, E>> for Graph<V, E> {
+    type Error = GraphError;
+
+    fn try_from(slots: GraphSlots<V, E>) -> Result<Self, Self::Error> {
+        Graph::from_slots(slots.verticies, slots.edges)
+    }
+}
+
 impl<V, E> Graph<V, E> {
     pub fn from_constraints() -> Graph<V, E> {
         Graph {
@@ -128,9 +231,7 @@ impl<V, E> Graph<V, E> {
             edges: GenVec::new(),
         }
     }
-}
 
-impl<V: Clone, E: Clone> Graph<V, E> {
     pub fn new() -> Graph<V, E> {
         Graph {
             verticies: GenVec::new(),
@@ -138,42 +239,366 @@ impl<V: Clone, E: Clone> Graph<V, E> {
         }
     }
 
+    /// Note: this will *not* be tracked, as recording it would need a copy of
+    /// `vertex_data`. No diff is made, so the add can't be undone, and a diff
+    /// log won't recreate the vertex when replayed. Use [`Graph::add_vertex`]
+    /// when the data is `Clone`.
+    pub fn add_vertex_untracked(&mut self, vertex_data: V) -> VertexIndex {
+        VertexIndex(self.verticies.add(Vertex::new(vertex_data)))
+    }
+
+    /// Note: this will *not* be tracked, as recording it would need a copy of
+    /// `edge_data`. No diff is made, so the add can't be undone, and a diff
+    /// log won't recreate the edge when replayed. Use [`Graph::add_edge`] when
+    /// the data is `Clone`.
+    pub fn add_edge_untracked(
+        &mut self,
+        from_index: VertexIndex,
+        to_index: VertexIndex,
+        edge_data: E,
+    ) -> Result<EdgeIndex, GraphError> {
+        self.assert_vertex_exists(from_index)?;
+        self.assert_vertex_exists(to_index)?;
+
+        // create the edge and link everything up
+        let edge_index = EdgeIndex(self.edges.add(Edge::new(from_index, to_index, edge_data)));
+
+        // connect the verticies (all vertex lookups use unwraps here to preserve the invariant of two-way connections)
+        self[from_index].add_to_unchecked(to_index, edge_index);
+        self[to_index].add_from_unchecked(from_index, edge_index);
+
+        Ok(edge_index)
+    }
+
+    /// Remove an edge. Its data is moved into the returned diff.
+    pub fn remove_edge(&mut self, edge_index: EdgeIndex) -> Result<GraphDiff<V, E>, GraphError> {
+        self.remove_edge_internal(edge_index)
+            .map(GraphDiff::RemoveEdge)
+    }
+
+    pub(crate) fn remove_edge_internal(
+        &mut self,
+        edge_index: EdgeIndex,
+    ) -> Result<RemoveEdge<E>, GraphError> {
+        self.remove_edge_reopening(edge_index, edge_index.0.generation + 1)
+            .map(|(removed, _)| removed)
+    }
+
+    /// Remove an edge, leaving its slot open at `open_generation`. Also
+    /// returns where it was in its endpoints' connection lists.
+    fn remove_edge_reopening(
+        &mut self,
+        edge_index: EdgeIndex,
+        open_generation: u32,
+    ) -> Result<(RemoveEdge<E>, ConnectionPositions), GraphError> {
+        let edge = self
+            .get_edge(edge_index)
+            .with_context(|| EdgeDoesNotExistSnafu { index: edge_index })?;
+
+        let from_index = edge.from;
+        let to_index = edge.to;
+
+        // remove the edge (all vertex lookups use unwraps here to preserve the invariant of two-way connections)
+        let positions = self.disconnect_edge(edge_index, from_index, to_index);
+
+        let edge = self
+            .edges
+            .remove_with_generation(edge_index.0, open_generation)
+            .unwrap();
+
+        let removed = RemoveEdge {
+            edge_index,
+            edge,
+            open_generation: graph_diff::open_generation(
+                open_generation,
+                edge_index.0.generation + 1,
+            ),
+        };
+
+        Ok((removed, positions))
+    }
+
+    /// Remove a vertex along with every edge connected to it. Their data is
+    /// moved into the returned diff.
+    pub fn remove_vertex(
+        &mut self,
+        vertex_index: VertexIndex,
+    ) -> Result<GraphDiff<V, E>, GraphError> {
+        self.remove_vertex_internal(vertex_index)
+            .map(GraphDiff::RemoveVertex)
+    }
+
+    pub(crate) fn remove_vertex_internal(
+        &mut self,
+        vertex_index: VertexIndex,
+    ) -> Result<RemoveVertex<V, E>, GraphError> {
+        self.remove_vertex_reopening(vertex_index, vertex_index.0.generation + 1)
+            .map(|(removed, _)| removed)
+    }
+
+    /// Remove a vertex and its edges, leaving its slot open at
+    /// `open_generation`. Also returns where each edge was in its endpoints'
+    /// connection lists.
+    fn remove_vertex_reopening(
+        &mut self,
+        vertex_index: VertexIndex,
+        open_generation: u32,
+    ) -> Result<(RemoveVertex<V, E>, Vec<ConnectionPositions>), GraphError> {
+        // check that everything is in proper order
+        let vertex = self
+            .get_vertex(vertex_index)
+            .with_context(|| VertexDoesNotExistSnafu {
+                index: vertex_index,
+            })?;
+
+        // remove all connections to the vertex
+        let connections = vertex.get_connected_edges();
+
+        let (edge_diffs, positions): (Vec<RemoveEdge<E>>, Vec<ConnectionPositions>) = connections
+            .iter()
+            .map(|edge_index| {
+                self.remove_edge_reopening(*edge_index, edge_index.0.generation + 1)
+                    .unwrap()
+            })
+            .unzip();
+
+        // finally remove the vertex
+        let vertex = self
+            .verticies
+            .remove_with_generation(vertex_index.0, open_generation)
+            .unwrap();
+
+        let removed = RemoveVertex {
+            vertex_index,
+            vertex,
+            removed_edges: edge_diffs,
+            open_generation: graph_diff::open_generation(
+                open_generation,
+                vertex_index.0.generation + 1,
+            ),
+        };
+
+        Ok((removed, positions))
+    }
+
+    pub fn apply_diff<VP: Patch<V>, EP: Patch<E>>(
+        &mut self,
+        diff: GraphDiff<V, E, VP, EP>,
+    ) -> Result<(), GraphError> {
+        self.apply_diff_undoable(diff).map(|_| ())
+    }
+
+    /// Apply a batch of diffs in order. Either all of them are applied, or
+    /// the graph is left exactly as it was and `DiffBatchFailed` reports which
+    /// diff (by position in `diffs`) could not be applied.
+    pub fn apply_diffs<I, VP: Patch<V>, EP: Patch<E>>(&mut self, diffs: I) -> Result<(), GraphError>
+    where
+        I: IntoIterator<Item = GraphDiff<V, E, VP, EP>>,
+    {
+        let diffs: Vec<GraphDiff<V, E, VP, EP>> = diffs.into_iter().collect();
+
+        self.run_atomic(diffs.into_iter().enumerate(), Self::apply_diff_undoable)
+            .map(|_| ())
+            .map_err(|(position, err)| GraphError::DiffBatchFailed {
+                position,
+                source: Box::new(err),
+            })
+    }
+
+    /// Roll back a batch of diffs. `diffs` is given in the order the diffs
+    /// were applied, and is rolled back in reverse. Either all of them are
+    /// rolled back, or the graph is left exactly as it was and
+    /// `DiffBatchFailed` reports which diff (by position in `diffs`) could not
+    /// be rolled back.
+    pub fn rollback_diffs<I, VP: Patch<V>, EP: Patch<E>>(
+        &mut self,
+        diffs: I,
+    ) -> Result<(), GraphError>
+    where
+        I: IntoIterator<Item = GraphDiff<V, E, VP, EP>>,
+    {
+        let diffs: Vec<GraphDiff<V, E, VP, EP>> = diffs.into_iter().collect();
+
+        self.run_atomic(
+            diffs.into_iter().enumerate().rev(),
+            Self::rollback_diff_undoable,
+        )
+        .map(|_| ())
+        .map_err(|(position, err)| GraphError::DiffBatchFailed {
+            position,
+            source: Box::new(err),
+        })
+    }
+
+    pub fn rollback_diff<VP: Patch<V>, EP: Patch<E>>(
+        &mut self,
+        diff: GraphDiff<V, E, VP, EP>,
+    ) -> Result<(), GraphError> {
+        self.rollback_diff_undoable(diff).map(|_| ())
+    }
+
+    fn apply_diff_undoable<VP: Patch<V>, EP: Patch<E>>(
+        &mut self,
+        diff: GraphDiff<V, E, VP, EP>,
+    ) -> Result<Undo<V, E, VP, EP>, GraphError> {
+        match diff {
+            GraphDiff::AddVertex(diff) => self.apply_add_vertex(diff),
+            GraphDiff::AddEdge(diff) => self.apply_add_edge(diff, None),
+            GraphDiff::UpdateVertexData(diff) => self.set_vertex_data(diff.index, diff.after),
+            GraphDiff::UpdateEdgeData(diff) => self.set_edge_data(diff.index, diff.after),
+            GraphDiff::RemoveEdge(diff) => self.apply_remove_edge(&diff),
+            GraphDiff::RemoveVertex(diff) => self.apply_remove_vertex(&diff),
+            GraphDiff::PatchVertexData(diff) => self.apply_vertex_patch(diff),
+            GraphDiff::PatchEdgeData(diff) => self.apply_edge_patch(diff),
+            GraphDiff::Group(diffs) => self
+                .run_atomic(diffs.into_iter().enumerate(), Self::apply_diff_undoable)
+                .map_err(|(_, err)| err),
+        }
+    }
+
+    fn rollback_diff_undoable<VP: Patch<V>, EP: Patch<E>>(
+        &mut self,
+        diff: GraphDiff<V, E, VP, EP>,
+    ) -> Result<Undo<V, E, VP, EP>, GraphError> {
+        match diff {
+            GraphDiff::AddVertex(diff) => self.rollback_add_vertex(&diff),
+            GraphDiff::AddEdge(diff) => self.rollback_add_edge(&diff),
+            GraphDiff::UpdateVertexData(diff) => self.set_vertex_data(diff.index, diff.before),
+            GraphDiff::UpdateEdgeData(diff) => self.set_edge_data(diff.index, diff.before),
+            GraphDiff::RemoveEdge(diff) => self.rollback_remove_edge(diff, None),
+            GraphDiff::RemoveVertex(diff) => self.rollback_remove_vertex(diff, None),
+            GraphDiff::PatchVertexData(diff) => self.revert_vertex_patch(diff),
+            GraphDiff::PatchEdgeData(diff) => self.revert_edge_patch(diff),
+            GraphDiff::Group(diffs) => self
+                .run_atomic(
+                    diffs.into_iter().enumerate().rev(),
+                    Self::rollback_diff_undoable,
+                )
+                .map_err(|(_, err)| err),
+        }
+    }
+
+    /// Runs `f` on each diff. If one fails, everything done so far is undone,
+    /// and the failing position is returned with the error.
+    fn run_atomic<D, I, F, VP: Patch<V>, EP: Patch<E>>(
+        &mut self,
+        diffs: I,
+        mut f: F,
+    ) -> Result<Undo<V, E, VP, EP>, (usize, GraphError)>
+    where
+        I: Iterator<Item = (usize, D)>,
+        F: FnMut(&mut Self, D) -> Result<Undo<V, E, VP, EP>, GraphError>,
+    {
+        let mut undos = Vec::new();
+
+        for (position, diff) in diffs {
+            match f(self, diff) {
+                Ok(undo) => undos.push(undo),
+                Err(err) => {
+                    self.undo(Undo::Group(undos));
+
+                    return Err((position, err));
+                }
+            }
+        }
+
+        Ok(Undo::Group(undos))
+    }
+
+    fn undo<VP: Patch<V>, EP: Patch<E>>(&mut self, undo: Undo<V, E, VP, EP>) {
+        let result = match undo {
+            Undo::AddVertex(diff) => self.apply_add_vertex(diff),
+            Undo::AddEdge(diff, positions) => self.apply_add_edge(diff, Some(positions)),
+            Undo::VacateVertex(index, vacancy) => self.vacate_vertex(index, vacancy),
+            Undo::VacateEdge(index, vacancy) => self.vacate_edge(index, vacancy),
+            Undo::RestoreVertex(diff, positions) => {
+                self.rollback_remove_vertex(diff, Some(positions))
+            }
+            Undo::RestoreEdge(diff, positions) => self.rollback_remove_edge(diff, Some(positions)),
+            Undo::SetVertexData(index, data) => self.set_vertex_data(index, data),
+            Undo::SetEdgeData(index, data) => self.set_edge_data(index, data),
+            Undo::ApplyVertexPatch(diff) => self.apply_vertex_patch(diff),
+            Undo::RevertVertexPatch(diff) => self.revert_vertex_patch(diff),
+            Undo::ApplyEdgePatch(diff) => self.apply_edge_patch(diff),
+            Undo::RevertEdgePatch(diff) => self.revert_edge_patch(diff),
+            Undo::Group(undos) => {
+                for undo in undos.into_iter().rev() {
+                    self.undo(undo);
+                }
+
+                Ok(Undo::Group(Vec::new()))
+            }
+        };
+
+        result.expect("Graph state has become corrupted while undoing a diff");
+    }
+}
+
+/// How to take back a diff that was just applied or rolled back. It holds the
+/// data the graph let go of, so nothing has to be cloned.
+enum Undo<V, E, VP, EP> {
+    AddVertex(AddVertex<V>),
+    /// Add, putting the edge back where it was in its endpoints' connections
+    AddEdge(AddEdge<E>, ConnectionPositions),
+    /// Remove, putting the slot back where it was in the free list
+    VacateVertex(VertexIndex, Vacancy),
+    VacateEdge(EdgeIndex, Vacancy),
+    /// One position for each removed edge
+    RestoreVertex(RemoveVertex<V, E>, Vec<ConnectionPositions>),
+    RestoreEdge(RemoveEdge<E>, ConnectionPositions),
+    SetVertexData(VertexIndex, V),
+    SetEdgeData(EdgeIndex, E),
+    ApplyVertexPatch(PatchVertexData<VP>),
+    RevertVertexPatch(PatchVertexData<VP>),
+    ApplyEdgePatch(PatchEdgeData<EP>),
+    RevertEdgePatch(PatchEdgeData<EP>),
+    Group(Vec<Undo<V, E, VP, EP>>),
+}
+
+/// Where an edge was in its endpoints' connection lists before it was
+/// removed, so it can be put back in the same place.
+#[derive(Debug, Clone, Copy)]
+struct ConnectionPositions {
+    /// In the `from` vertex's `connections_to`
+    from: usize,
+    /// In the `to` vertex's `connections_from`
+    to: usize,
+}
+
+impl<V: Clone, E: Clone> Graph<V, E> {
+    /// Add a vertex, returning the diff that records it. The data ends up in
+    /// both the graph and the diff, hence `Clone`. Data that isn't `Clone` can
+    /// only be added with [`Graph::add_vertex_untracked`], which can't be
+    /// undone or replayed.
     pub fn add_vertex(&mut self, vertex_data: V) -> (VertexIndex, GraphDiff<V, E>) {
-        let vertex_index = VertexIndex(self.verticies.add(Vertex::new(vertex_data.clone())));
+        let vertex_index = self.add_vertex_untracked(vertex_data.clone());
 
         let diff = AddVertex {
             vertex_index,
             vertex_data,
+            open_generation: None,
         };
 
         (vertex_index, GraphDiff::AddVertex(diff))
     }
 
+    /// Add an edge, returning the diff that records it. Like
+    /// [`Graph::add_vertex`], data that isn't `Clone` can only be added with
+    /// [`Graph::add_edge_untracked`], which can't be undone or replayed.
     pub fn add_edge(
         &mut self,
         from_index: VertexIndex,
         to_index: VertexIndex,
         edge_data: E,
     ) -> Result<(EdgeIndex, GraphDiff<V, E>), GraphError> {
-        self.assert_vertex_exists(from_index)?;
-        self.assert_vertex_exists(to_index)?;
-
-        // create the edge and link everything up
-        let edge_index = EdgeIndex(self.edges.add(Edge::new(
-            from_index,
-            to_index,
-            edge_data.clone(),
-        )));
-
-        // connect the verticies (all vertex lookups use unwraps here to preserve the invariant of two-way connections)
-        self[from_index].add_to_unchecked(to_index, edge_index);
-        self[to_index].add_from_unchecked(from_index, edge_index);
+        let edge_index = self.add_edge_untracked(from_index, to_index, edge_data.clone())?;
 
         let diff = AddEdge {
-            edge_index: edge_index,
+            edge_index,
             from: from_index,
             to: to_index,
             edge_data,
+            open_generation: None,
         };
 
         Ok((edge_index, GraphDiff::AddEdge(diff)))
@@ -221,100 +646,126 @@ impl<V: Clone, E: Clone> Graph<V, E> {
         ))
     }
 
-    pub fn remove_edge(
+    /// Mutate a vertex's data in place, recording the change as an
+    /// `UpdateVertexData` diff. Returns whatever `f` returns.
+    pub fn update_vertex_with<T, F>(
         &mut self,
-        edge_index: EdgeIndex,
-    ) -> Result<(E, GraphDiff<V, E>), GraphError> {
-        self.remove_edge_internal(edge_index)
-            .map(|(edge, diff)| (edge, GraphDiff::RemoveEdge(diff)))
+        index: VertexIndex,
+        f: F,
+    ) -> Result<(T, GraphDiff<V, E>), GraphError>
+    where
+        F: FnOnce(&mut V) -> T,
+    {
+        let vertex = self
+            .get_vertex_mut(index)
+            .context(VertexDoesNotExistSnafu { index })?;
+
+        let before = vertex.data.clone();
+        let result = f(&mut vertex.data);
+
+        Ok((
+            result,
+            GraphDiff::UpdateVertexData(UpdateVertexData {
+                index,
+                before,
+                after: vertex.data.clone(),
+            }),
+        ))
     }
 
-    fn remove_edge_internal(
+    /// Mutate an edge's data in place, recording the change as an
+    /// `UpdateEdgeData` diff. Returns whatever `f` returns.
+    pub fn update_edge_with<T, F>(
         &mut self,
-        edge_index: EdgeIndex,
-    ) -> Result<(E, RemoveEdge<E>), GraphError> {
+        index: EdgeIndex,
+        f: F,
+    ) -> Result<(T, GraphDiff<V, E>), GraphError>
+    where
+        F: FnOnce(&mut E) -> T,
+    {
         let edge = self
-            .get_edge(edge_index)
-            .with_context(|| EdgeDoesNotExistSnafu { index: edge_index })?;
-
-        let from_index = edge.from;
-        let to_index = edge.to;
-
-        // remove the edge (all vertex lookups use unwraps here to preserve the invariant of two-way connections)
-        self[from_index].remove_to(edge_index).unwrap();
-        self[to_index].remove_from(edge_index).unwrap();
-
-        let edge = self.edges.remove(edge_index.0).unwrap();
-        let edge_data = edge.data.clone();
+            .get_edge_mut(index)
+            .context(EdgeDoesNotExistSnafu { index })?;
 
-        let diff = RemoveEdge {
-            edge_index,
-            edge: edge,
-        };
+        let before = edge.data.clone();
+        let result = f(&mut edge.data);
 
-        Ok((edge_data, diff))
+        Ok((
+            result,
+            GraphDiff::UpdateEdgeData(UpdateEdgeData {
+                index,
+                before,
+                after: edge.data.clone(),
+            }),
+        ))
     }
 
-    pub fn remove_vertex(
+    /// Same as [`Graph::apply_diff`], but only clones the data that ends up in
+    /// the graph, so the diff can be kept around (e.g. in a redo stack).
+    pub fn apply_diff_ref<VP: Patch<V>, EP: Patch<E>>(
         &mut self,
-        vertex_index: VertexIndex,
-    ) -> Result<(V, GraphDiff<V, E>), GraphError> {
-        // check that everything is in proper order
-        let vertex = self
-            .get_vertex(vertex_index)
-            .with_context(|| VertexDoesNotExistSnafu {
-                index: vertex_index,
-            })?;
-
-        // remove all connections to the vertex
-        let mut connections = vertex.get_connections_from().clone();
-        connections.extend(vertex.get_connections_to().clone());
-
-        let edge_diffs: Vec<RemoveEdge<E>> = connections
-            .iter()
-            .map(|(_, connection_index)| self.remove_edge_internal(*connection_index).unwrap().1)
-            .collect();
-
-        // finally remove the vertex
-        let vertex = self.verticies.remove(vertex_index.0).unwrap();
-        let vertex_data = vertex.data.clone();
-
-        let diff = GraphDiff::RemoveVertex(RemoveVertex {
-            vertex_index,
-            vertex,
-            removed_edges: edge_diffs,
-        });
+        diff: &GraphDiff<V, E, VP, EP>,
+    ) -> Result<(), GraphError> {
+        self.apply_diff_ref_undoable(diff).map(|_| ())
+    }
 
-        Ok((vertex_data, diff))
+    /// Same as [`Graph::rollback_diff`], but only clones the data that ends up
+    /// in the graph, so the diff can be kept around (e.g. in an undo stack).
+    pub fn rollback_diff_ref<VP: Patch<V>, EP: Patch<E>>(
+        &mut self,
+        diff: &GraphDiff<V, E, VP, EP>,
+    ) -> Result<(), GraphError> {
+        self.rollback_diff_ref_undoable(diff).map(|_| ())
     }
 
-    pub fn apply_diff(&mut self, diff: GraphDiff<V, E>) -> Result<(), GraphError> {
+    fn apply_diff_ref_undoable<VP: Patch<V>, EP: Patch<E>>(
+        &mut self,
+        diff: &GraphDiff<V, E, VP, EP>,
+    ) -> Result<Undo<V, E, VP, EP>, GraphError> {
         match diff {
-            GraphDiff::AddVertex(diff) => self.apply_add_vertex_diff(diff),
-            GraphDiff::AddEdge(diff) => self.apply_add_edge_diff(diff),
-            GraphDiff::UpdateVertexData(diff) => self.apply_update_vertex_diff(diff),
-            GraphDiff::UpdateEdgeData(diff) => self.apply_update_edge_diff(diff),
-            GraphDiff::RemoveEdge(diff) => self.apply_remove_edge_diff(diff),
-            GraphDiff::RemoveVertex(diff) => self.apply_remove_vertex_diff(diff),
+            GraphDiff::AddVertex(diff) => self.apply_add_vertex(diff.clone()),
+            GraphDiff::AddEdge(diff) => self.apply_add_edge(diff.clone(), None),
+            GraphDiff::UpdateVertexData(diff) => {
+                self.set_vertex_data(diff.index, diff.after.clone())
+            }
+            GraphDiff::UpdateEdgeData(diff) => self.set_edge_data(diff.index, diff.after.clone()),
+            GraphDiff::RemoveEdge(diff) => self.apply_remove_edge(diff),
+            GraphDiff::RemoveVertex(diff) => self.apply_remove_vertex(diff),
+            GraphDiff::PatchVertexData(diff) => self.apply_vertex_patch(diff.clone()),
+            GraphDiff::PatchEdgeData(diff) => self.apply_edge_patch(diff.clone()),
+            GraphDiff::Group(diffs) => self
+                .run_atomic(diffs.iter().enumerate(), Self::apply_diff_ref_undoable)
+                .map_err(|(_, err)| err),
         }
     }
 
-    pub fn rollback_diff(&mut self, diff: GraphDiff<V, E>) -> Result<(), GraphError> {
+    fn rollback_diff_ref_undoable<VP: Patch<V>, EP: Patch<E>>(
+        &mut self,
+        diff: &GraphDiff<V, E, VP, EP>,
+    ) -> Result<Undo<V, E, VP, EP>, GraphError> {
         match diff {
-            GraphDiff::AddVertex(add_vertex) => self.rollback_add_vertex_diff(add_vertex),
-            GraphDiff::AddEdge(add_edge) => self.rollback_add_edge_diff(add_edge),
-            GraphDiff::UpdateVertexData(diff) => self.rollback_update_vertex_diff(diff),
-            GraphDiff::UpdateEdgeData(diff) => self.rollback_update_edge_diff(diff),
-            GraphDiff::RemoveEdge(remove_edge) => self.rollback_remove_edge_diff(remove_edge),
-            GraphDiff::RemoveVertex(remove_vertex) => {
-                self.rollback_remove_vertex_diff(remove_vertex)
+            GraphDiff::AddVertex(diff) => self.rollback_add_vertex(diff),
+            GraphDiff::AddEdge(diff) => self.rollback_add_edge(diff),
+            GraphDiff::UpdateVertexData(diff) => {
+                self.set_vertex_data(diff.index, diff.before.clone())
             }
+            GraphDiff::UpdateEdgeData(diff) => self.set_edge_data(diff.index, diff.before.clone()),
+            GraphDiff::RemoveEdge(diff) => self.rollback_remove_edge(diff.clone(), None),
+            GraphDiff::RemoveVertex(diff) => self.rollback_remove_vertex(diff.clone(), None),
+            GraphDiff::PatchVertexData(diff) => self.revert_vertex_patch(diff.clone()),
+            GraphDiff::PatchEdgeData(diff) => self.revert_edge_patch(diff.clone()),
+            GraphDiff::Group(diffs) => self
+                .run_atomic(
+                    diffs.iter().enumerate().rev(),
+                    Self::rollback_diff_ref_undoable,
+                )
+                .map_err(|(_, err)| err),
         }
     }
 }
 
 // Utility functions
-impl<V: Clone, E: Clone> Graph<V, E> {
+impl<V, E> Graph<V, E> {
     pub fn get_vertex(&self, index: VertexIndex) -> Option<&Vertex<V>> {
         self.verticies.get(index.0)
     }
@@ -328,7 +779,7 @@ impl<V: Clone, E: Clone> Graph<V, E> {
     }
 
     /// Note: changes here will *not* be tracked. It's usually better to use
-    /// [`Graph::update_vertex`]
+    /// [`Graph::update_vertex`] or [`Graph::update_vertex_with`]
     pub fn get_vertex_data_mut(&mut self, index: VertexIndex) -> Option<&mut V> {
         Some(&mut self.get_vertex_mut(index)?.data)
     }
@@ -342,7 +793,7 @@ impl<V: Clone, E: Clone> Graph<V, E> {
     }
 
     /// Note: changes here will *not* be tracked. It's usually better to use
-    /// [`Graph::update_edge`]
+    /// [`Graph::update_edge`] or [`Graph::update_edge_with`]
     pub fn get_edge_data_mut(&mut self, index: EdgeIndex) -> Option<&mut E> {
         Some(&mut self.get_edge_mut(index)?.data)
     }
@@ -436,235 +887,433 @@ impl<V: Clone, E: Clone> Graph<V, E> {
             .map(|(index, edge)| (EdgeIndex(index), &mut edge.data))
     }
 
-    fn apply_add_vertex_diff(&mut self, diff: AddVertex<V>) -> Result<(), GraphError> {
-        if !self
-            .verticies
-            .is_replaceable_by_index_apply(diff.vertex_index.0)
-        {
-            return Err(GraphError::InvalidDiff);
-        }
+    fn apply_add_vertex<VP, EP>(
+        &mut self,
+        diff: AddVertex<V>,
+    ) -> Result<Undo<V, E, VP, EP>, GraphError> {
+        let index = diff.vertex_index;
+        self.check_vertex_slot_open(index, diff.get_open_generation())?;
 
-        self.verticies.vec[diff.vertex_index.0.index] = Element::Occupied {
-            value: Vertex::new(diff.vertex_data),
-            generation: diff.vertex_index.0.generation,
-        };
+        let vacancy = self
+            .verticies
+            .occupy_vacancy(index.0, Vertex::new(diff.vertex_data))
+            .unwrap_or_else(|_| panic!("Graph state has become corrupted before applying diff"));
 
-        Ok(())
+        Ok(Undo::VacateVertex(index, vacancy))
     }
 
-    fn apply_add_edge_diff(&mut self, diff: AddEdge<E>) -> Result<(), GraphError> {
-        self.assert_vertex_exists(diff.from)?;
-        self.assert_vertex_exists(diff.to)?;
+    /// Connections are added at `positions`, or at the end.
+    fn apply_add_edge<VP, EP>(
+        &mut self,
+        diff: AddEdge<E>,
+        positions: Option<ConnectionPositions>,
+    ) -> Result<Undo<V, E, VP, EP>, GraphError> {
+        let (index, from, to) = (diff.edge_index, diff.from, diff.to);
+
+        self.check_endpoint_exists(index, from)?;
+        self.check_endpoint_exists(index, to)?;
 
         // check that this edge doesn't exist
-        if !self.edges.is_replaceable_by_index_apply(diff.edge_index.0) {
-            return Err(GraphError::InvalidDiff);
-        }
+        self.check_edge_slot_open(index, diff.get_open_generation())?;
 
         // apply the diff
-        self.edges.vec[diff.edge_index.0.index] = Element::Occupied {
-            value: Edge::new(diff.from, diff.to, diff.edge_data),
-            generation: diff.edge_index.0.generation,
-        };
+        let vacancy = self
+            .edges
+            .occupy_vacancy(index.0, Edge::new(from, to, diff.edge_data))
+            .unwrap_or_else(|_| panic!("Graph state has become corrupted before applying diff"));
+        self.connect_edge(index, from, to, positions);
 
-        let from = self
-            .get_vertex_mut(diff.from)
-            .expect("Graph state has become corrupted before applying diff");
-        from.add_to_unchecked(diff.to, diff.edge_index);
+        Ok(Undo::VacateEdge(index, vacancy))
+    }
 
-        let to = self
-            .get_vertex_mut(diff.to)
-            .expect("Graph state has become corrupted before applying diff");
-        to.add_from_unchecked(diff.from, diff.edge_index);
+    fn set_vertex_data<VP, EP>(
+        &mut self,
+        index: VertexIndex,
+        data: V,
+    ) -> Result<Undo<V, E, VP, EP>, GraphError> {
+        let vertex = self
+            .get_vertex_mut(index)
+            .context(VertexDoesNotExistSnafu { index })?;
 
-        Ok(())
+        let old_data = mem::replace(&mut vertex.data, data);
+
+        Ok(Undo::SetVertexData(index, old_data))
     }
 
-    fn apply_update_vertex_diff(&mut self, diff: UpdateVertexData<V>) -> Result<(), GraphError> {
+    fn set_edge_data<VP, EP>(
+        &mut self,
+        index: EdgeIndex,
+        data: E,
+    ) -> Result<Undo<V, E, VP, EP>, GraphError> {
+        let edge = self
+            .get_edge_mut(index)
+            .context(EdgeDoesNotExistSnafu { index })?;
+
+        let old_data = mem::replace(&mut edge.data, data);
+
+        Ok(Undo::SetEdgeData(index, old_data))
+    }
+
+    fn apply_vertex_patch<VP: Patch<V>, EP: Patch<E>>(
+        &mut self,
+        diff: PatchVertexData<VP>,
+    ) -> Result<Undo<V, E, VP, EP>, GraphError> {
         let vertex = self
             .get_vertex_mut(diff.index)
             .context(VertexDoesNotExistSnafu { index: diff.index })?;
 
-        vertex.data = diff.after;
+        if diff.reverted {
+            diff.patch.revert(&mut vertex.data);
+        } else {
+            diff.patch.apply(&mut vertex.data);
+        }
 
-        Ok(())
+        Ok(Undo::RevertVertexPatch(diff))
+    }
+
+    fn revert_vertex_patch<VP: Patch<V>, EP: Patch<E>>(
+        &mut self,
+        diff: PatchVertexData<VP>,
+    ) -> Result<Undo<V, E, VP, EP>, GraphError> {
+        let vertex = self
+            .get_vertex_mut(diff.index)
+            .context(VertexDoesNotExistSnafu { index: diff.index })?;
+
+        if diff.reverted {
+            diff.patch.apply(&mut vertex.data);
+        } else {
+            diff.patch.revert(&mut vertex.data);
+        }
+
+        Ok(Undo::ApplyVertexPatch(diff))
+    }
+
+    fn apply_edge_patch<VP: Patch<V>, EP: Patch<E>>(
+        &mut self,
+        diff: PatchEdgeData<EP>,
+    ) -> Result<Undo<V, E, VP, EP>, GraphError> {
+        let edge = self
+            .get_edge_mut(diff.index)
+            .context(EdgeDoesNotExistSnafu { index: diff.index })?;
+
+        if diff.reverted {
+            diff.patch.revert(&mut edge.data);
+        } else {
+            diff.patch.apply(&mut edge.data);
+        }
+
+        Ok(Undo::RevertEdgePatch(diff))
     }
 
-    fn apply_update_edge_diff(&mut self, diff: UpdateEdgeData<E>) -> Result<(), GraphError> {
+    fn revert_edge_patch<VP: Patch<V>, EP: Patch<E>>(
+        &mut self,
+        diff: PatchEdgeData<EP>,
+    ) -> Result<Undo<V, E, VP, EP>, GraphError> {
         let edge = self
             .get_edge_mut(diff.index)
             .context(EdgeDoesNotExistSnafu { index: diff.index })?;
 
-        edge.data = diff.after;
+        if diff.reverted {
+            diff.patch.apply(&mut edge.data);
+        } else {
+            diff.patch.revert(&mut edge.data);
+        }
 
-        Ok(())
+        Ok(Undo::ApplyEdgePatch(diff))
     }
 
-    fn apply_remove_edge_diff(&mut self, diff: RemoveEdge<E>) -> Result<(), GraphError> {
-        self.assert_vertex_exists(diff.edge.from)?;
-        self.assert_vertex_exists(diff.edge.to)?;
-        self.assert_edge_exists(diff.edge_index)?;
+    fn apply_remove_edge<VP, EP>(
+        &mut self,
+        diff: &RemoveEdge<E>,
+    ) -> Result<Undo<V, E, VP, EP>, GraphError> {
+        let index = diff.edge_index;
+        self.check_edge_endpoints(index, diff.edge.from, diff.edge.to)?;
 
         // remove the edge
-        self.remove_edge(diff.edge_index)
+        let (removed, positions) = self
+            .remove_edge_reopening(index, diff.get_open_generation())
             .expect("Graph state has become corrupted before applying diff");
 
-        Ok(())
+        Ok(Undo::RestoreEdge(removed, positions))
     }
 
-    fn apply_remove_vertex_diff(&mut self, diff: RemoveVertex<V, E>) -> Result<(), GraphError> {
-        self.assert_vertex_exists(diff.vertex_index)?;
+    fn apply_remove_vertex<VP, EP>(
+        &mut self,
+        diff: &RemoveVertex<V, E>,
+    ) -> Result<Undo<V, E, VP, EP>, GraphError> {
+        let index = diff.vertex_index;
+        self.assert_vertex_exists(index)?;
 
-        self.remove_vertex(diff.vertex_index)
+        let (removed, positions) = self
+            .remove_vertex_reopening(index, diff.get_open_generation())
             .expect("Graph state has become corrupted before applying diff");
 
-        Ok(())
+        Ok(Undo::RestoreVertex(removed, positions))
     }
 
-    fn rollback_add_vertex_diff(&mut self, diff: AddVertex<V>) -> Result<(), GraphError> {
+    fn rollback_add_vertex<VP, EP>(
+        &mut self,
+        diff: &AddVertex<V>,
+    ) -> Result<Undo<V, E, VP, EP>, GraphError> {
+        let index = diff.vertex_index;
+
         // check that the vertex exists
-        self.assert_vertex_exists(diff.vertex_index)?;
+        self.assert_vertex_exists(index)?;
 
-        self.remove_vertex_and_reset(diff.vertex_index)
+        let vertex_data = self
+            .remove_vertex_and_reset(index, diff.get_open_generation())
             .expect("Graph state has become corrupted before applying diff");
 
-        Ok(())
+        Ok(Undo::AddVertex(AddVertex {
+            vertex_index: index,
+            vertex_data,
+            open_generation: diff.open_generation,
+        }))
     }
 
-    fn rollback_add_edge_diff(&mut self, diff: AddEdge<E>) -> Result<(), GraphError> {
-        self.assert_vertex_exists(diff.from)?;
-        self.assert_vertex_exists(diff.to)?;
-        self.assert_edge_exists(diff.edge_index)?;
+    fn rollback_add_edge<VP, EP>(
+        &mut self,
+        diff: &AddEdge<E>,
+    ) -> Result<Undo<V, E, VP, EP>, GraphError> {
+        let index = diff.edge_index;
+        self.check_edge_endpoints(index, diff.from, diff.to)?;
 
         // remove the edge
-        self.remove_edge_and_reset(diff.edge_index)
+        let (edge_data, positions) = self
+            .remove_edge_and_reset(index, diff.get_open_generation())
             .expect("Graph state has become corrupted before applying diff");
 
-        Ok(())
+        Ok(Undo::AddEdge(
+            AddEdge {
+                edge_index: index,
+                from: diff.from,
+                to: diff.to,
+                edge_data,
+                open_generation: diff.open_generation,
+            },
+            positions,
+        ))
     }
 
-    fn rollback_update_vertex_diff(&mut self, diff: UpdateVertexData<V>) -> Result<(), GraphError> {
-        let vertex = self
-            .get_vertex_mut(diff.index)
-            .context(VertexDoesNotExistSnafu { index: diff.index })?;
+    /// Connections are added at `positions`, or at the end.
+    fn rollback_remove_edge<VP, EP>(
+        &mut self,
+        diff: RemoveEdge<E>,
+        positions: Option<ConnectionPositions>,
+    ) -> Result<Undo<V, E, VP, EP>, GraphError> {
+        let index = diff.edge_index;
+        let from_index = diff.edge.from;
+        let to_index = diff.edge.to;
 
-        vertex.data = diff.before;
+        self.check_endpoint_exists(index, from_index)?;
+        self.check_endpoint_exists(index, to_index)?;
 
-        Ok(())
+        // check that this edge doesn't exist
+        self.check_edge_slot_open(index, diff.get_open_generation())?;
+
+        // apply the diff
+        let vacancy = self
+            .edges
+            .occupy_vacancy(index.0, diff.edge)
+            .unwrap_or_else(|_| panic!("Graph state has become corrupted before applying diff"));
+        self.connect_edge(index, from_index, to_index, positions);
+
+        Ok(Undo::VacateEdge(index, vacancy))
     }
 
-    fn rollback_update_edge_diff(&mut self, diff: UpdateEdgeData<E>) -> Result<(), GraphError> {
-        let edge = self
-            .get_edge_mut(diff.index)
-            .context(EdgeDoesNotExistSnafu { index: diff.index })?;
+    /// Connections are added at `positions` (one for each removed edge), or
+    /// at the end.
+    fn rollback_remove_vertex<VP, EP>(
+        &mut self,
+        diff: RemoveVertex<V, E>,
+        positions: Option<Vec<ConnectionPositions>>,
+    ) -> Result<Undo<V, E, VP, EP>, GraphError> {
+        self.check_vertex_slot_open(diff.vertex_index, diff.get_open_generation())?;
 
-        edge.data = diff.before;
+        // check that all edges are replaceable, and connect to something
+        for removed_edge in diff.removed_edges.iter() {
+            let edge_index = removed_edge.edge_index;
 
-        Ok(())
-    }
+            self.check_edge_slot_open(edge_index, removed_edge.get_open_generation())?;
 
-    fn rollback_remove_edge_diff(&mut self, diff: RemoveEdge<E>) -> Result<(), GraphError> {
-        let from_index = diff.edge.from;
-        let to_index = diff.edge.to;
+            for endpoint in [removed_edge.edge.from, removed_edge.edge.to] {
+                if endpoint != diff.vertex_index {
+                    self.check_endpoint_exists(edge_index, endpoint)?;
+                }
+            }
+        }
 
-        self.assert_vertex_exists(from_index)?;
-        self.assert_vertex_exists(to_index)?;
+        let vacancy = self
+            .verticies
+            .occupy_vacancy(diff.vertex_index.0, diff.vertex)
+            .unwrap_or_else(|_| panic!("Graph state has become corrupted before applying diff"));
 
-        // check that this edge doesn't exist
-        if !self
-            .edges
-            .is_replaceable_by_index_rollback(diff.edge_index.0)
-        {
-            return Err(GraphError::InvalidDiff);
+        let mut undos = vec![Undo::VacateVertex(diff.vertex_index, vacancy)];
+        let mut connections = Vec::new();
+        for removed_edge in diff.removed_edges {
+            let (index, from, to) = (
+                removed_edge.edge_index,
+                removed_edge.edge.from,
+                removed_edge.edge.to,
+            );
+
+            let vacancy = self
+                .edges
+                .occupy_vacancy(index.0, removed_edge.edge)
+                .unwrap_or_else(|_| {
+                    panic!("Graph state has become corrupted before applying diff")
+                });
+            undos.push(Undo::VacateEdge(index, vacancy));
+            connections.push((index, from, to));
         }
 
-        // apply the diff
-        self.edges.vec[diff.edge_index.0.index] = Element::Occupied {
-            value: diff.edge,
-            generation: diff.edge_index.0.generation,
-        };
+        match positions {
+            // the edges were removed one after another, so they have to be
+            // put back in reverse for the positions to line up
+            Some(positions) => {
+                for ((index, from, to), positions) in connections.into_iter().zip(positions).rev() {
+                    self.connect_edge(index, from, to, Some(positions));
+                }
+            }
+            None => {
+                for (index, from, to) in connections {
+                    self.connect_edge(index, from, to, None);
+                }
+            }
+        }
 
-        let from = self
-            .get_vertex_mut(from_index)
-            .expect("Graph state has become corrupted before applying diff");
-        from.add_to_unchecked(to_index, diff.edge_index);
+        Ok(Undo::Group(undos))
+    }
 
-        let to = self
-            .get_vertex_mut(to_index)
-            .expect("Graph state has become corrupted before applying diff");
-        to.add_from_unchecked(from_index, diff.edge_index);
+    fn vacate_vertex<VP, EP>(
+        &mut self,
+        index: VertexIndex,
+        vacancy: Vacancy,
+    ) -> Result<Undo<V, E, VP, EP>, GraphError> {
+        self.assert_vertex_exists(index)?;
 
-        Ok(())
+        self.verticies.vacate(index.0.index, vacancy);
+
+        Ok(Undo::Group(Vec::new()))
     }
 
-    fn rollback_remove_vertex_diff(&mut self, diff: RemoveVertex<V, E>) -> Result<(), GraphError> {
-        if !self
-            .verticies
-            .is_replaceable_by_index_rollback(diff.vertex_index.0)
-        {
-            return Err(GraphError::InvalidDiff);
+    fn vacate_edge<VP, EP>(
+        &mut self,
+        index: EdgeIndex,
+        vacancy: Vacancy,
+    ) -> Result<Undo<V, E, VP, EP>, GraphError> {
+        let edge = self
+            .get_edge(index)
+            .context(EdgeDoesNotExistSnafu { index })?;
+        let from_index = edge.from;
+        let to_index = edge.to;
+
+        self[from_index].remove_to(index).unwrap();
+        self[to_index].remove_from(index).unwrap();
+
+        self.edges.vacate(index.0.index, vacancy);
+
+        Ok(Undo::Group(Vec::new()))
+    }
+
+    /// Check that a vertex slot is open, at the generation it would be at if
+    /// the diff being applied is valid.
+    fn check_vertex_slot_open(&self, index: VertexIndex, expected: u32) -> Result<(), GraphError> {
+        match self.verticies.open_generation(index.0.index) {
+            None => Err(GraphError::VertexSlotOccupied { index }),
+            Some(actual) if actual != expected => Err(GraphError::VertexGenerationMismatch {
+                index,
+                expected,
+                actual,
+            }),
+            Some(_) => Ok(()),
         }
+    }
 
-        // check that all edges are replaceable
-        for removed_edge in diff.removed_edges.iter() {
-            if !self
-                .edges
-                .is_replaceable_by_index_rollback(removed_edge.edge_index.0)
-            {
-                return Err(GraphError::InvalidDiff);
-            }
+    /// Check that an edge slot is open, at the generation it would be at if
+    /// the diff being applied is valid.
+    fn check_edge_slot_open(&self, index: EdgeIndex, expected: u32) -> Result<(), GraphError> {
+        match self.edges.open_generation(index.0.index) {
+            None => Err(GraphError::EdgeSlotOccupied { index }),
+            Some(actual) if actual != expected => Err(GraphError::EdgeGenerationMismatch {
+                index,
+                expected,
+                actual,
+            }),
+            Some(_) => Ok(()),
         }
+    }
 
-        self.verticies.vec[diff.vertex_index.0.index] = Element::Occupied {
-            value: diff.vertex,
-            generation: diff.vertex_index.0.generation,
-        };
+    fn check_endpoint_exists(
+        &self,
+        edge: EdgeIndex,
+        vertex: VertexIndex,
+    ) -> Result<(), GraphError> {
+        if self.verticies.get(vertex.0).is_none() {
+            Err(GraphError::DanglingEndpoint { edge, vertex })
+        } else {
+            Ok(())
+        }
+    }
 
-        for removed_edge in diff.removed_edges {
-            let from = self
-                .get_vertex_mut(removed_edge.edge.from)
-                .expect("Graph state has become corrupted before applying diff");
-            from.add_to_unchecked(removed_edge.edge.to, removed_edge.edge_index);
-
-            let to = self
-                .get_vertex_mut(removed_edge.edge.to)
-                .expect("Graph state has become corrupted before applying diff");
-            to.add_from_unchecked(removed_edge.edge.from, removed_edge.edge_index);
-
-            self.edges.vec[removed_edge.edge_index.0.index] = Element::Occupied {
-                value: removed_edge.edge,
-                generation: removed_edge.edge_index.0.generation,
-            };
+    /// Check that an edge exists, and connects the verticies the diff says it
+    /// does.
+    fn check_edge_endpoints(
+        &self,
+        index: EdgeIndex,
+        from: VertexIndex,
+        to: VertexIndex,
+    ) -> Result<(), GraphError> {
+        let edge = self
+            .get_edge(index)
+            .context(EdgeDoesNotExistSnafu { index })?;
+
+        if edge.from != from || edge.to != to {
+            return Err(GraphError::EdgeEndpointsMismatch {
+                index,
+                expected_from: from,
+                expected_to: to,
+                actual_from: edge.from,
+                actual_to: edge.to,
+            });
         }
 
         Ok(())
     }
 
-    fn remove_vertex_and_reset(&mut self, index: VertexIndex) -> Result<V, GraphError> {
+    fn remove_vertex_and_reset(
+        &mut self,
+        index: VertexIndex,
+        open_generation: u32,
+    ) -> Result<V, GraphError> {
         // check that everything is in proper order
         let vertex = self
             .get_vertex(index)
             .with_context(|| VertexDoesNotExistSnafu { index })?;
 
         // remove all connections to the vertex
-        let mut connections = vertex.get_connections_from().clone();
-        connections.extend(vertex.get_connections_to().clone());
+        let connections = vertex.get_connected_edges();
 
-        for (_, edge_index) in connections {
-            self.remove_edge_and_reset(edge_index).unwrap();
+        for edge_index in connections {
+            self.remove_edge_and_reset(edge_index, edge_index.0.generation)
+                .unwrap();
         }
 
         // finally remove the vertex
         let vertex = self
             .verticies
-            .remove_but_maintain_generation(index.0)
+            .remove_with_generation(index.0, open_generation)
             .unwrap();
-        let vertex_data = vertex.data.clone();
 
-        Ok(vertex_data)
+        Ok(vertex.data)
     }
 
-    fn remove_edge_and_reset(&mut self, edge_index: EdgeIndex) -> Result<E, GraphError> {
+    /// Also returns where the edge was in its endpoints' connection lists.
+    fn remove_edge_and_reset(
+        &mut self,
+        edge_index: EdgeIndex,
+        open_generation: u32,
+    ) -> Result<(E, ConnectionPositions), GraphError> {
         let edge = self
             .get_edge(edge_index)
             .with_context(|| EdgeDoesNotExistSnafu { index: edge_index })?;
@@ -672,28 +1321,67 @@ impl<V: Clone, E: Clone> Graph<V, E> {
         let to_index = edge.to;
 
         // remove the edge (all vertex lookups use unwraps here to preserve the invariant of two-way connections)
-        let from = self.get_vertex_mut(from_index).unwrap();
-        from.remove_to(edge_index).unwrap();
-
-        let to = self.get_vertex_mut(to_index).unwrap();
-        to.remove_from(edge_index).unwrap();
+        let positions = self.disconnect_edge(edge_index, from_index, to_index);
 
         let edge = self
             .edges
-            .remove_but_maintain_generation(edge_index.0)
+            .remove_with_generation(edge_index.0, open_generation)
             .unwrap();
 
-        Ok(edge.data)
+        Ok((edge.data, positions))
+    }
+
+    /// Add an edge to its endpoints' connection lists, at `positions` if it's
+    /// being put back where it was, otherwise at the end.
+    fn connect_edge(
+        &mut self,
+        index: EdgeIndex,
+        from: VertexIndex,
+        to: VertexIndex,
+        positions: Option<ConnectionPositions>,
+    ) {
+        let positions = positions.unwrap_or_else(|| ConnectionPositions {
+            from: self[from].connections_to.len(),
+            to: self[to].connections_from.len(),
+        });
+
+        let from_vertex = self
+            .get_vertex_mut(from)
+            .expect("Graph state has become corrupted before applying diff");
+        from_vertex
+            .connections_to
+            .insert(positions.from, (to, index));
+
+        let to_vertex = self
+            .get_vertex_mut(to)
+            .expect("Graph state has become corrupted before applying diff");
+        to_vertex
+            .connections_from
+            .insert(positions.to, (from, index));
+    }
+
+    /// Remove an edge from its endpoints' connection lists, returning where it
+    /// was.
+    fn disconnect_edge(
+        &mut self,
+        index: EdgeIndex,
+        from: VertexIndex,
+        to: VertexIndex,
+    ) -> ConnectionPositions {
+        ConnectionPositions {
+            from: self[from].remove_to(index).unwrap(),
+            to: self[to].remove_from(index).unwrap(),
+        }
     }
 }
 
-impl<V: Clone, E: Clone> Default for Graph<V, E> {
+impl<V, E> Default for Graph<V, E> {
     fn default() -> Self {
         Graph::new()
     }
 }
 
-impl<V: Clone, E: Clone> ops::Index<VertexIndex> for Graph<V, E> {
+impl<V, E> ops::Index<VertexIndex> for Graph<V, E> {
     type Output = Vertex<V>;
 
     fn index(&self, index: VertexIndex) -> &Self::Output {
@@ -701,13 +1389,13 @@ impl<V: Clone, E: Clone> ops::Index<VertexIndex> for Graph<V, E> {
     }
 }
 
-impl<V: Clone, E: Clone> ops::IndexMut<VertexIndex> for Graph<V, E> {
+impl<V, E> ops::IndexMut<VertexIndex> for Graph<V, E> {
     fn index_mut(&mut self, index: VertexIndex) -> &mut Self::Output {
         self.get_vertex_mut(index).unwrap()
     }
 }
 
-impl<V: Clone, E: Clone> ops::Index<EdgeIndex> for Graph<V, E> {
+impl<V, E> ops::Index<EdgeIndex> for Graph<V, E> {
     type Output = Edge<E>;
 
     fn index(&self, index: EdgeIndex) -> &Self::Output {
@@ -715,7 +1403,7 @@ impl<V: Clone, E: Clone> ops::Index<EdgeIndex> for Graph<V, E> {
     }
 }
 
-impl<V: Clone, E: Clone> ops::IndexMut<EdgeIndex> for Graph<V, E> {
+impl<V, E> ops::IndexMut<EdgeIndex> for Graph<V, E> {
     fn index_mut(&mut self, index: EdgeIndex) -> &mut Self::Output {
         self.get_edge_mut(index).unwrap()
     }
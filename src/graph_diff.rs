@@ -1,11 +1,27 @@
-use alloc::vec::Vec;
+use alloc::{format, string::String, vec, vec::Vec};
 
-use crate::graph::{Edge, EdgeIndex, Vertex, VertexIndex};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    graph::{Edge, EdgeIndex, GraphIndex, Vertex, VertexIndex},
+    patch::NoPatch,
+    validate::SlotKind,
+};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "js_names", serde(rename_all = "camelCase"))]
 pub struct AddVertex<V> {
     pub(crate) vertex_index: VertexIndex,
     pub(crate) vertex_data: V,
+    /// Generation the slot is open at beforehand, if it isn't the vertex's
+    /// own (see [`GraphDiff::invert`]).
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub(crate) open_generation: Option<u32>,
 }
 
 impl<V> AddVertex<V> {
@@ -16,14 +32,30 @@ impl<V> AddVertex<V> {
     pub fn get_vertex_data(&self) -> &V {
         &self.vertex_data
     }
+
+    /// Generation the slot is open at before the vertex is added, and after
+    /// it's rolled back.
+    pub fn get_open_generation(&self) -> u32 {
+        self.open_generation
+            .unwrap_or(self.vertex_index.0.generation)
+    }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "js_names", serde(rename_all = "camelCase"))]
 pub struct AddEdge<E> {
     pub(crate) edge_index: EdgeIndex,
     pub(crate) from: VertexIndex,
     pub(crate) to: VertexIndex,
     pub(crate) edge_data: E,
+    /// Generation the slot is open at beforehand, if it isn't the edge's own
+    /// (see [`GraphDiff::invert`]).
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub(crate) open_generation: Option<u32>,
 }
 
 impl<E> AddEdge<E> {
@@ -42,12 +74,27 @@ impl<E> AddEdge<E> {
     pub fn get_edge_data(&self) -> &E {
         &self.edge_data
     }
+
+    /// Generation the slot is open at before the edge is added, and after
+    /// it's rolled back.
+    pub fn get_open_generation(&self) -> u32 {
+        self.open_generation.unwrap_or(self.edge_index.0.generation)
+    }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "js_names", serde(rename_all = "camelCase"))]
 pub struct RemoveEdge<E> {
     pub(crate) edge_index: EdgeIndex,
     pub(crate) edge: Edge<E>,
+    /// Generation the slot is left open at, if it isn't the one after the
+    /// edge's (see [`GraphDiff::invert`]).
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub(crate) open_generation: Option<u32>,
 }
 
 impl<E> RemoveEdge<E> {
@@ -58,13 +105,42 @@ impl<E> RemoveEdge<E> {
     pub fn get_edge(&self) -> &Edge<E> {
         &self.edge
     }
+
+    /// Generation the slot is left open at after the edge is removed, and is
+    /// expected to be open at when it's rolled back.
+    pub fn get_open_generation(&self) -> u32 {
+        self.open_generation
+            .unwrap_or(self.edge_index.0.generation + 1)
+    }
+
+    fn invert(self) -> AddEdge<E> {
+        AddEdge {
+            edge_index: self.edge_index,
+            from: self.edge.from,
+            to: self.edge.to,
+            open_generation: open_generation(
+                self.get_open_generation(),
+                self.edge_index.0.generation,
+            ),
+            edge_data: self.edge.data,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "js_names", serde(rename_all = "camelCase"))]
 pub struct RemoveVertex<V, E> {
     pub(crate) vertex_index: VertexIndex,
     pub(crate) vertex: Vertex<V>,
     pub(crate) removed_edges: Vec<RemoveEdge<E>>,
+    /// Generation the slot is left open at, if it isn't the one after the
+    /// vertex's (see [`GraphDiff::invert`]).
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub(crate) open_generation: Option<u32>,
 }
 
 impl<V, E> RemoveVertex<V, E> {
@@ -79,9 +155,18 @@ impl<V, E> RemoveVertex<V, E> {
     pub fn get_removed_edges(&self) -> &Vec<RemoveEdge<E>> {
         &self.removed_edges
     }
+
+    /// Generation the slot is left open at after the vertex is removed, and
+    /// is expected to be open at when it's rolled back.
+    pub fn get_open_generation(&self) -> u32 {
+        self.open_generation
+            .unwrap_or(self.vertex_index.0.generation + 1)
+    }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "js_names", serde(rename_all = "camelCase"))]
 pub struct UpdateVertexData<V> {
     pub(crate) index: VertexIndex,
     pub(crate) before: V,
@@ -99,6 +184,8 @@ impl<V> UpdateVertexData<V> {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "js_names", serde(rename_all = "camelCase"))]
 pub struct UpdateEdgeData<E> {
     pub(crate) index: EdgeIndex,
     pub(crate) before: E,
@@ -115,12 +202,349 @@ impl<E> UpdateEdgeData<E> {
     }
 }
 
+/// An update that only stores a [`crate::Patch`] instead of the whole before
+/// and after data.
 #[derive(Debug, Clone)]
-pub enum GraphDiff<V, E> {
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "js_names", serde(rename_all = "camelCase"))]
+pub struct PatchVertexData<P> {
+    pub(crate) index: VertexIndex,
+    pub(crate) patch: P,
+    /// Whether applying this reverts the patch (see [`GraphDiff::invert`]).
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "core::ops::Not::not")
+    )]
+    pub(crate) reverted: bool,
+}
+
+impl<P> PatchVertexData<P> {
+    pub fn get_index(&self) -> VertexIndex {
+        self.index
+    }
+
+    pub fn get_patch(&self) -> &P {
+        &self.patch
+    }
+}
+
+/// An update that only stores a [`crate::Patch`] instead of the whole before
+/// and after data.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "js_names", serde(rename_all = "camelCase"))]
+pub struct PatchEdgeData<P> {
+    pub(crate) index: EdgeIndex,
+    pub(crate) patch: P,
+    /// Whether applying this reverts the patch (see [`GraphDiff::invert`]).
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "core::ops::Not::not")
+    )]
+    pub(crate) reverted: bool,
+}
+
+impl<P> PatchEdgeData<P> {
+    pub fn get_index(&self) -> EdgeIndex {
+        self.index
+    }
+
+    pub fn get_patch(&self) -> &P {
+        &self.patch
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "js_names", serde(tag = "variant", content = "data"))]
+/// A change to a graph, that can be applied and rolled back.
+///
+/// `VP` and `EP` are the [`crate::Patch`] types of vertex and edge data (see
+/// [`crate::Patchable`]). Graphs that don't patch their data can leave them
+/// as [`NoPatch`].
+pub enum GraphDiff<V, E, VP = NoPatch, EP = NoPatch> {
     AddVertex(AddVertex<V>),
     AddEdge(AddEdge<E>),
     RemoveEdge(RemoveEdge<E>),
     RemoveVertex(RemoveVertex<V, E>),
     UpdateVertexData(UpdateVertexData<V>),
     UpdateEdgeData(UpdateEdgeData<E>),
+    PatchVertexData(PatchVertexData<VP>),
+    PatchEdgeData(PatchEdgeData<EP>),
+    /// Several diffs that are applied in order, and rolled back in reverse
+    /// order, as one unit. See [`crate::Transaction`].
+    Group(Vec<GraphDiff<V, E, VP, EP>>),
+}
+
+impl<V, E> GraphDiff<V, E> {
+    /// Turn a diff without patches into one that can be kept alongside diffs
+    /// with patches, e.g. in the same log.
+    pub fn with_patches<VP, EP>(self) -> GraphDiff<V, E, VP, EP> {
+        match self {
+            GraphDiff::AddVertex(diff) => GraphDiff::AddVertex(diff),
+            GraphDiff::AddEdge(diff) => GraphDiff::AddEdge(diff),
+            GraphDiff::RemoveEdge(diff) => GraphDiff::RemoveEdge(diff),
+            GraphDiff::RemoveVertex(diff) => GraphDiff::RemoveVertex(diff),
+            GraphDiff::UpdateVertexData(diff) => GraphDiff::UpdateVertexData(diff),
+            GraphDiff::UpdateEdgeData(diff) => GraphDiff::UpdateEdgeData(diff),
+            GraphDiff::PatchVertexData(diff) => match diff.patch {},
+            GraphDiff::PatchEdgeData(diff) => match diff.patch {},
+            GraphDiff::Group(diffs) => {
+                GraphDiff::Group(diffs.into_iter().map(GraphDiff::with_patches).collect())
+            }
+        }
+    }
+}
+
+impl<V, E, VP, EP> GraphDiff<V, E, VP, EP> {
+    /// Produce the opposite diff: applying it undoes `self`, and rolling it
+    /// back redoes `self`.
+    ///
+    /// Adds and removes turn into each other, recording the generation the
+    /// slot is open at so it's left exactly as the original diff found it.
+    /// Removing a vertex also removes its edges, so that turns into a group
+    /// adding the vertex and then its edges. Updates swap `before` and
+    /// `after`, patches are reverted instead of applied, and groups invert
+    /// each diff in reverse order.
+    pub fn invert(self) -> GraphDiff<V, E, VP, EP> {
+        match self {
+            GraphDiff::AddVertex(diff) => GraphDiff::RemoveVertex(RemoveVertex {
+                vertex_index: diff.vertex_index,
+                open_generation: open_generation(
+                    diff.get_open_generation(),
+                    diff.vertex_index.0.generation + 1,
+                ),
+                vertex: Vertex::new(diff.vertex_data),
+                removed_edges: Vec::new(),
+            }),
+            GraphDiff::AddEdge(diff) => GraphDiff::RemoveEdge(RemoveEdge {
+                edge_index: diff.edge_index,
+                open_generation: open_generation(
+                    diff.get_open_generation(),
+                    diff.edge_index.0.generation + 1,
+                ),
+                edge: Edge::new(diff.from, diff.to, diff.edge_data),
+            }),
+            GraphDiff::RemoveEdge(diff) => GraphDiff::AddEdge(diff.invert()),
+            GraphDiff::RemoveVertex(diff) => {
+                let add_vertex = GraphDiff::AddVertex(AddVertex {
+                    vertex_index: diff.vertex_index,
+                    open_generation: open_generation(
+                        diff.get_open_generation(),
+                        diff.vertex_index.0.generation,
+                    ),
+                    vertex_data: diff.vertex.data,
+                });
+
+                if diff.removed_edges.is_empty() {
+                    add_vertex
+                } else {
+                    // the edges were removed first, so they're added last
+                    let mut diffs = vec![add_vertex];
+                    diffs.extend(
+                        diff.removed_edges
+                            .into_iter()
+                            .rev()
+                            .map(|removed_edge| GraphDiff::AddEdge(removed_edge.invert())),
+                    );
+
+                    GraphDiff::Group(diffs)
+                }
+            }
+            GraphDiff::UpdateVertexData(diff) => GraphDiff::UpdateVertexData(UpdateVertexData {
+                index: diff.index,
+                before: diff.after,
+                after: diff.before,
+            }),
+            GraphDiff::UpdateEdgeData(diff) => GraphDiff::UpdateEdgeData(UpdateEdgeData {
+                index: diff.index,
+                before: diff.after,
+                after: diff.before,
+            }),
+            GraphDiff::PatchVertexData(diff) => GraphDiff::PatchVertexData(PatchVertexData {
+                reverted: !diff.reverted,
+                ..diff
+            }),
+            GraphDiff::PatchEdgeData(diff) => GraphDiff::PatchEdgeData(PatchEdgeData {
+                reverted: !diff.reverted,
+                ..diff
+            }),
+            GraphDiff::Group(diffs) => {
+                GraphDiff::Group(diffs.into_iter().rev().map(GraphDiff::invert).collect())
+            }
+        }
+    }
+
+    /// Summarise the diff for people, e.g. "added 2 vertices, removed 1 edge".
+    /// Edges removed along with a vertex are counted too.
+    pub fn describe(&self) -> String {
+        let mut counts = ChangeCounts::default();
+        self.count_changes(&mut counts);
+
+        let parts: Vec<String> = [
+            ("added", counts.added),
+            ("removed", counts.removed),
+            ("updated", counts.updated),
+        ]
+        .into_iter()
+        .flat_map(|(action, [verticies, edges])| {
+            [
+                (action, verticies, "vertex", "vertices"),
+                (action, edges, "edge", "edges"),
+            ]
+        })
+        .filter(|(_, count, _, _)| *count > 0)
+        .map(|(action, count, singular, plural)| {
+            format!(
+                "{} {} {}",
+                action,
+                count,
+                if count == 1 { singular } else { plural }
+            )
+        })
+        .collect();
+
+        if parts.is_empty() {
+            String::from("no changes")
+        } else {
+            parts.join(", ")
+        }
+    }
+
+    fn count_changes(&self, counts: &mut ChangeCounts) {
+        match self {
+            GraphDiff::AddVertex(_) => counts.added[0] += 1,
+            GraphDiff::AddEdge(_) => counts.added[1] += 1,
+            GraphDiff::RemoveEdge(_) => counts.removed[1] += 1,
+            GraphDiff::RemoveVertex(diff) => {
+                counts.removed[0] += 1;
+                counts.removed[1] += diff.removed_edges.len();
+            }
+            GraphDiff::UpdateVertexData(_) | GraphDiff::PatchVertexData(_) => {
+                counts.updated[0] += 1
+            }
+            GraphDiff::UpdateEdgeData(_) | GraphDiff::PatchEdgeData(_) => counts.updated[1] += 1,
+            GraphDiff::Group(diffs) => {
+                for diff in diffs {
+                    diff.count_changes(counts);
+                }
+            }
+        }
+    }
+
+    /// Call `f` with every slot this diff reads or writes, including the
+    /// endpoints of any edges it touches. Slots may be reported more than once.
+    pub(crate) fn for_each_slot<F: FnMut(SlotKind, usize)>(&self, f: &mut F) {
+        self.for_each_access(&mut |index, _| {
+            let (kind, slot) = index.slot();
+            f(kind, slot)
+        });
+    }
+
+    /// Like [`GraphDiff::for_each_slot`], also saying how each slot is used.
+    pub(crate) fn for_each_access<F: FnMut(GraphIndex, SlotAccess)>(&self, f: &mut F) {
+        let edge = |f: &mut F, edge_index: EdgeIndex, from: VertexIndex, to: VertexIndex| {
+            f(GraphIndex::Edge(edge_index), SlotAccess::Existence);
+            f(GraphIndex::Vertex(from), SlotAccess::Endpoint);
+            f(GraphIndex::Vertex(to), SlotAccess::Endpoint);
+        };
+
+        match self {
+            GraphDiff::AddVertex(diff) => {
+                f(GraphIndex::Vertex(diff.vertex_index), SlotAccess::Existence)
+            }
+            GraphDiff::AddEdge(diff) => edge(f, diff.edge_index, diff.from, diff.to),
+            GraphDiff::RemoveEdge(diff) => edge(f, diff.edge_index, diff.edge.from, diff.edge.to),
+            GraphDiff::RemoveVertex(diff) => {
+                f(GraphIndex::Vertex(diff.vertex_index), SlotAccess::Existence);
+
+                for removed_edge in &diff.removed_edges {
+                    edge(
+                        f,
+                        removed_edge.edge_index,
+                        removed_edge.edge.from,
+                        removed_edge.edge.to,
+                    );
+                }
+            }
+            GraphDiff::UpdateVertexData(diff) => {
+                f(GraphIndex::Vertex(diff.index), SlotAccess::Data)
+            }
+            GraphDiff::UpdateEdgeData(diff) => f(GraphIndex::Edge(diff.index), SlotAccess::Data),
+            GraphDiff::PatchVertexData(diff) => f(GraphIndex::Vertex(diff.index), SlotAccess::Data),
+            GraphDiff::PatchEdgeData(diff) => f(GraphIndex::Edge(diff.index), SlotAccess::Data),
+            GraphDiff::Group(diffs) => {
+                for diff in diffs {
+                    diff.for_each_access(f);
+                }
+            }
+        }
+    }
+
+    /// Indices this diff uses that `later` (applied after it) has changed in a
+    /// way that stops this diff from being rolled back on its own. Indices are
+    /// compared by slot, so a slot that's been reused counts as changed.
+    pub(crate) fn conflicts_with(&self, later: &GraphDiff<V, E, VP, EP>) -> Vec<GraphIndex> {
+        let mut later_accesses = Vec::new();
+        later.for_each_access(&mut |index, access| later_accesses.push((index.slot(), access)));
+
+        let mut conflicts = Vec::new();
+        self.for_each_access(&mut |index, access| {
+            let blocked = later_accesses.iter().any(|(slot, later_access)| {
+                *slot == index.slot() && access.conflicts_with(*later_access)
+            });
+
+            if blocked && !conflicts.contains(&index) {
+                conflicts.push(index);
+            }
+        });
+
+        conflicts
+    }
+
+    /// Whether this diff reads or writes the given slot.
+    pub(crate) fn touches_slot(&self, kind: SlotKind, slot: usize) -> bool {
+        let mut touches = false;
+        self.for_each_slot(&mut |other_kind, other_slot| {
+            touches |= other_kind == kind && other_slot == slot;
+        });
+
+        touches
+    }
+}
+
+/// `generation`, unless it's the one a diff would assume anyway.
+pub(crate) fn open_generation(generation: u32, default: u32) -> Option<u32> {
+    (generation != default).then_some(generation)
+}
+
+/// How a diff uses a slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SlotAccess {
+    /// Adds or removes whatever is in the slot.
+    Existence,
+    /// Changes the data in the slot.
+    Data,
+    /// Connects an edge to the vertex in the slot.
+    Endpoint,
+}
+
+impl SlotAccess {
+    /// Whether rolling back an earlier access is unsafe after a later one.
+    fn conflicts_with(self, later: SlotAccess) -> bool {
+        match (self, later) {
+            (SlotAccess::Existence, _) => true,
+            (SlotAccess::Data, later) => later != SlotAccess::Endpoint,
+            (SlotAccess::Endpoint, later) => later == SlotAccess::Existence,
+        }
+    }
+}
+
+/// Number of (verticies, edges) changed in each way, for
+/// [`GraphDiff::describe`].
+#[derive(Default)]
+struct ChangeCounts {
+    added: [usize; 2],
+    removed: [usize; 2],
+    updated: [usize; 2],
 }
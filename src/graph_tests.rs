@@ -3,7 +3,7 @@ use std::println;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
-use crate::{gen_vec::Element, graph::Graph, GenVec};
+use crate::{gen_vec::Element, graph::Graph, GenVec, Index, Recorder};
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 struct Foo {
@@ -46,11 +46,13 @@ fn test_gen_vec_serde() {
         vec![
             Element::Open {
                 generation: 0,
-                next: None
+                next: None,
+                prev: Some(1)
             },
             Element::Open {
                 generation: 0,
-                next: Some(0)
+                next: Some(0),
+                prev: None
             },
             Element::Occupied {
                 generation: 1,
@@ -98,7 +100,7 @@ fn test_undo_redo() {
     let (first, diff_1) = graph.add_vertex(2);
     println!("{:?}", graph);
 
-    let (_, diff_2) = graph.remove_vertex(first).unwrap();
+    let diff_2 = graph.remove_vertex(first).unwrap();
     let (second, diff_3) = graph.add_vertex(4);
 
     // test that using the diffs in the wrong order produce correct results
@@ -139,10 +141,10 @@ fn test_undo_redo_edges() {
         .add_edge(second_vertex, third_vertex, "second_edge".into())
         .unwrap();
 
-    let (_, diff_6) = graph.remove_vertex(third_vertex).unwrap();
+    let diff_6 = graph.remove_vertex(third_vertex).unwrap();
     assert!(matches!(graph.get_edge(second_edge), None));
 
-    let (_, diff_7) = graph.remove_edge(first_edge).unwrap();
+    let diff_7 = graph.remove_edge(first_edge).unwrap();
     assert!(matches!(graph.get_edge(first_edge), None));
 
     graph.rollback_diff(diff_7.clone()).unwrap();
@@ -233,3 +235,1207 @@ fn test_custom_serde() {
         index
     );
 }
+
+#[test]
+fn test_transaction() {
+    let mut graph: Graph<String, String> = Graph::new();
+
+    let (first_vertex, _) = graph.add_vertex("first_vertex".into());
+
+    let (second_vertex, diff) = graph
+        .transaction(|tx| {
+            let second_vertex = tx.add_vertex("second_vertex".into());
+            tx.add_edge(first_vertex, second_vertex, "edge".into())?;
+            tx.update_vertex(first_vertex, "first_vertex_modified".into())?;
+            tx.remove_vertex(first_vertex)?;
+
+            Ok(second_vertex)
+        })
+        .unwrap();
+
+    assert!(graph.get_vertex(first_vertex).is_none());
+    assert!(graph.get_edges().is_empty());
+
+    // the whole group is rolled back in reverse order
+    graph.rollback_diff(diff.clone()).unwrap();
+    assert!(graph.get_vertex(second_vertex).is_none());
+    assert_eq!(
+        *graph.get_vertex_data(first_vertex).unwrap(),
+        "first_vertex".to_string()
+    );
+
+    graph.apply_diff(diff.clone()).unwrap();
+    assert!(graph.get_vertex(first_vertex).is_none());
+    graph.get_vertex(second_vertex).unwrap();
+
+    // applying the group again fails, and leaves the graph untouched
+    graph.rollback_diff(diff.clone()).unwrap();
+    let diff_2 = graph.remove_vertex(first_vertex).unwrap();
+    graph.apply_diff(diff.clone()).unwrap_err();
+    assert!(graph.get_vertex(second_vertex).is_none());
+
+    graph.rollback_diff(diff_2).unwrap();
+    graph.apply_diff(diff).unwrap();
+}
+
+#[test]
+fn test_transaction_abort() {
+    let mut graph: Graph<i32, ()> = Graph::new();
+
+    let (first, _) = graph.add_vertex(1);
+
+    let mut tx = graph.begin_transaction();
+    let second = tx.add_vertex(2);
+    tx.add_edge(first, second, ()).unwrap();
+    tx.remove_vertex(first).unwrap();
+    tx.abort();
+
+    assert_eq!(*graph.get_vertex_data(first).unwrap(), 1);
+    assert!(graph.get_vertex(second).is_none());
+    assert!(graph.get_edges().is_empty());
+
+    // errors inside the closure abort the transaction
+    graph
+        .transaction(|tx| {
+            tx.update_vertex(first, 3)?;
+            tx.remove_vertex(second)?;
+
+            Ok(())
+        })
+        .unwrap_err();
+
+    assert_eq!(*graph.get_vertex_data(first).unwrap(), 1);
+
+    // the freed slot is reused with the same generation as before
+    let (third, _) = graph.add_vertex(3);
+    assert_eq!(third, second);
+}
+
+#[test]
+fn test_transaction_rollback_failure() {
+    use crate::recorder::sealed::Record;
+
+    let mut graph: Graph<i32, ()> = Graph::new();
+
+    // rolling back works like aborting
+    let mut tx = graph.begin_transaction();
+    let first = tx.add_vertex(1);
+    tx.rollback().unwrap();
+    assert!(graph.get_vertex(first).is_none());
+
+    // corrupt the graph behind the transaction's back
+    let mut tx = graph.begin_transaction();
+    let second = tx.add_vertex(2);
+    tx.recorded_graph().remove_vertex(second).unwrap();
+    assert!(tx.rollback().is_err());
+
+    // dropping doesn't panic, even though it can't roll back either
+    let mut tx = graph.begin_transaction();
+    let third = tx.add_vertex(3);
+    tx.recorded_graph().remove_vertex(third).unwrap();
+    drop(tx);
+}
+
+#[test]
+fn test_apply_diffs_atomic() {
+    use crate::GraphError;
+
+    let mut graph: Graph<i32, ()> = Graph::new();
+
+    let (first, diff_1) = graph.add_vertex(1);
+    let (second, diff_2) = graph.add_vertex(2);
+    let (_, diff_3) = graph.add_edge(first, second, ()).unwrap();
+    let diff_4 = graph.remove_vertex(first).unwrap();
+
+    let diffs = vec![diff_1, diff_2, diff_3, diff_4];
+
+    graph.rollback_diffs(diffs.clone()).unwrap();
+    assert!(graph.get_verticies().is_empty());
+
+    // the last diff is invalid, so nothing should be applied
+    let mut broken = diffs.clone();
+    broken.push(broken[0].clone());
+
+    match graph.apply_diffs(broken.clone()) {
+        Err(GraphError::DiffBatchFailed { position, .. }) => assert_eq!(position, 4),
+        other => panic!("expected batch failure, got {:?}", other),
+    }
+    assert!(graph.get_verticies().is_empty());
+    assert!(graph.get_edges().is_empty());
+
+    graph.apply_diffs(diffs.clone()).unwrap();
+    assert!(graph.get_vertex(first).is_none());
+    assert_eq!(*graph.get_vertex_data(second).unwrap(), 2);
+
+    // the removed vertex's slot gets reused, so it can't be restored
+    let (_, diff_5) = graph.add_vertex(3);
+    match graph.rollback_diffs(diffs.clone()) {
+        Err(GraphError::DiffBatchFailed { position, .. }) => assert_eq!(position, 3),
+        other => panic!("expected batch failure, got {:?}", other),
+    }
+    assert!(graph.get_vertex(first).is_none());
+    assert_eq!(graph.get_verticies().len(), 2);
+
+    graph.rollback_diff(diff_5).unwrap();
+    graph.rollback_diffs(diffs).unwrap();
+    assert!(graph.get_verticies().is_empty());
+}
+
+#[test]
+fn test_diff_serde() {
+    use crate::GraphDiff;
+
+    let mut graph: Graph<String, String> = Graph::new();
+
+    let (first_vertex, diff_1) = graph.add_vertex("first_vertex".into());
+    let (second_vertex, diff_2) = graph.add_vertex("second_vertex".into());
+    let (_, diff_3) = graph
+        .add_edge(first_vertex, second_vertex, "edge".into())
+        .unwrap();
+    let (_, diff_4) = graph
+        .update_vertex(first_vertex, "modified".into())
+        .unwrap();
+    let diff_5 = graph.remove_vertex(second_vertex).unwrap();
+
+    let diffs = vec![diff_1, diff_2, diff_3, diff_4, diff_5];
+    let serialized = serde_json::to_string(&diffs).unwrap();
+    let deserialized: Vec<GraphDiff<String, String>> = serde_json::from_str(&serialized).unwrap();
+
+    graph.rollback_diffs(deserialized.clone()).unwrap();
+    assert!(graph.get_verticies().is_empty());
+
+    graph.apply_diffs(deserialized).unwrap();
+    assert_eq!(graph.get_vertex_data(first_vertex).unwrap(), "modified");
+    assert!(graph.get_vertex(second_vertex).is_none());
+    assert!(graph.get_edges().is_empty());
+}
+
+#[test]
+fn test_gen_vec_full_fidelity_serde() {
+    use crate::full_fidelity::FullFidelity;
+
+    let mut vec: GenVec<Foo> = GenVec::new();
+
+    let first = vec.add(Foo { x: "foo".into() });
+    let second = vec.add(Foo { x: "foo".into() });
+    let _third = vec.add(Foo { x: "foo".into() });
+
+    vec.remove(second);
+    vec.remove(first);
+
+    let serialized = serde_json::to_value(FullFidelity(&vec)).unwrap();
+    let FullFidelity(deserialized): FullFidelity<GenVec<Foo>> =
+        serde_json::from_value(serialized).unwrap();
+
+    assert_eq!(deserialized.vec, vec.vec);
+    assert_eq!(deserialized.next_open_slot, vec.next_open_slot);
+
+    // free lists that point at occupied slots are rejected
+    let broken = json!({
+        "slots": [
+            { "Occupied": { "value": { "x": "foo" }, "generation": 0 } },
+            { "Open": { "generation": 1, "next": 0 } },
+        ],
+        "next_open_slot": 1,
+    });
+    serde_json::from_value::<FullFidelity<GenVec<Foo>>>(broken).unwrap_err();
+}
+
+#[test]
+fn test_graph_full_fidelity_serde() {
+    use crate::full_fidelity::FullFidelity;
+
+    let mut graph: Graph<String, String> = Graph::new();
+
+    let (first_vertex, _) = graph.add_vertex("first_vertex".into());
+    let (second_vertex, _) = graph.add_vertex("second_vertex".into());
+    let (edge, _) = graph
+        .add_edge(first_vertex, second_vertex, "edge".into())
+        .unwrap();
+    let diff = graph.remove_vertex(second_vertex).unwrap();
+
+    let serialized = serde_json::to_string(&FullFidelity(&graph)).unwrap();
+    let FullFidelity(mut deserialized): FullFidelity<Graph<String, String>> =
+        serde_json::from_str(&serialized).unwrap();
+
+    // generations of the removed vertex and edge survived, so the diff can
+    // still be rolled back
+    deserialized.rollback_diff(diff).unwrap();
+    assert_eq!(
+        deserialized.get_vertex_data(second_vertex).unwrap(),
+        "second_vertex"
+    );
+    assert_eq!(deserialized.get_edge_data(edge).unwrap(), "edge");
+}
+
+#[test]
+fn test_gen_vec_occupy() {
+    let mut vec: GenVec<i32> = GenVec::new();
+
+    let first = vec.add(1);
+    let second = vec.add(2);
+    let third = vec.add(3);
+
+    vec.remove(first);
+    vec.remove(second);
+    vec.remove(third);
+
+    // take the middle of the free list
+    let second_again = Index {
+        index: second.index,
+        generation: 5,
+    };
+    vec.occupy(second_again, 20).unwrap();
+    assert_eq!(vec.occupy(second_again, 21), Err(21));
+
+    // stale indices to the removed value must not become valid again
+    assert_eq!(vec.occupy(first, 10), Err(10));
+    assert_eq!(vec.get(first), None);
+
+    let fourth = vec.add(4);
+    let fifth = vec.add(5);
+    assert_ne!(fourth.index, second.index);
+    assert_ne!(fifth.index, second.index);
+    assert_eq!(vec[second_again], 20);
+
+    // occupying past the end adds the skipped slots to the free list
+    let far = Index {
+        index: 5,
+        generation: 0,
+    };
+    vec.occupy(far, 50).unwrap();
+    assert_eq!(vec.add(3).index, 4);
+    assert_eq!(vec.add(4).index, 3);
+    assert_eq!(vec.add(6).index, 6);
+    assert_eq!(vec[far], 50);
+}
+
+#[test]
+fn test_rollback_keeps_free_list_consistent() {
+    let mut graph: Graph<i32, ()> = Graph::new();
+
+    let (first, _) = graph.add_vertex(1);
+    let (second, _) = graph.add_vertex(2);
+
+    let diff_1 = graph.remove_vertex(first).unwrap();
+    graph.remove_vertex(second).unwrap();
+
+    // restore a slot that isn't at the front of the free list, then make sure
+    // new verticies don't land on top of it
+    graph.rollback_diff(diff_1).unwrap();
+
+    let (third, _) = graph.add_vertex(3);
+    let (fourth, _) = graph.add_vertex(4);
+
+    assert_ne!(third.0.index, first.0.index);
+    assert_ne!(fourth.0.index, first.0.index);
+    assert_eq!(*graph.get_vertex_data(first).unwrap(), 1);
+    assert_eq!(*graph.get_vertex_data(third).unwrap(), 3);
+    assert_eq!(*graph.get_vertex_data(fourth).unwrap(), 4);
+}
+
+#[test]
+fn test_failed_batch_keeps_free_list_order() {
+    use crate::{graph_diff::AddVertex, GraphDiff, VertexIndex};
+
+    let mut graph: Graph<i32, i32> = Graph::new();
+
+    let verticies: Vec<_> = (0..4).map(|i| graph.add_vertex_untracked(i)).collect();
+    graph
+        .add_edge_untracked(verticies[2], verticies[3], 23)
+        .unwrap();
+
+    graph.remove_vertex(verticies[1]).unwrap();
+    let diff_3 = graph.remove_vertex(verticies[3]).unwrap();
+    graph.remove_vertex(verticies[0]).unwrap();
+
+    let before = graph.clone();
+
+    // restoring the vertex takes a slot (and an edge slot) out of the middle
+    // of the free list before the batch fails
+    assert!(graph.rollback_diffs([diff_3.clone(), diff_3]).is_err());
+
+    // adding past the end grows the vec
+    let grow: GraphDiff<i32, i32> = GraphDiff::AddVertex(AddVertex {
+        vertex_index: VertexIndex(Index {
+            index: 6,
+            generation: 0,
+        }),
+        vertex_data: 6,
+        open_generation: None,
+    });
+    assert!(graph.apply_diffs([grow.clone(), grow]).is_err());
+
+    assert_eq!(graph.verticies.vec.len(), before.verticies.vec.len());
+
+    let mut expected = before.clone();
+    for i in 0..4 {
+        assert_eq!(
+            graph.add_vertex_untracked(i),
+            expected.add_vertex_untracked(i)
+        );
+    }
+    assert_eq!(
+        graph
+            .add_edge_untracked(verticies[2], verticies[2], 0)
+            .unwrap(),
+        expected
+            .add_edge_untracked(verticies[2], verticies[2], 0)
+            .unwrap()
+    );
+
+    // connections go back to where they were too
+    let mut graph: Graph<i32, i32> = Graph::new();
+    let hub = graph.add_vertex_untracked(0);
+    let spokes: Vec<_> = (1..4).map(|i| graph.add_vertex_untracked(i)).collect();
+    let mut adds = Vec::new();
+    for spoke in spokes.iter() {
+        adds.push(graph.add_edge(hub, *spoke, 0).unwrap());
+        graph.add_edge_untracked(*spoke, hub, 0).unwrap();
+    }
+    graph.add_edge_untracked(hub, hub, 0).unwrap();
+
+    let connections = |graph: &Graph<i32, i32>| -> Vec<_> {
+        graph
+            .vertex_indexes()
+            .map(|index| {
+                (
+                    graph[index].get_connections_from().clone(),
+                    graph[index].get_connections_to().clone(),
+                )
+            })
+            .collect()
+    };
+    let before = connections(&graph);
+
+    let mut scratch = graph.clone();
+    let remove_edge = scratch.remove_edge(adds[1].0).unwrap();
+    let remove_spoke = scratch.remove_vertex(spokes[0]).unwrap();
+    let remove_hub = scratch.remove_vertex(hub).unwrap();
+
+    // the hub can only be removed once
+    assert!(graph
+        .apply_diffs([remove_edge, remove_spoke, remove_hub.clone(), remove_hub])
+        .is_err());
+    assert_eq!(connections(&graph), before);
+
+    // rolling back an edge in the middle of the hub's connections
+    let add_edge = adds[1].1.clone();
+    assert!(graph.rollback_diffs([add_edge.clone(), add_edge]).is_err());
+    assert_eq!(connections(&graph), before);
+}
+
+#[test]
+fn test_replay_into_empty_graph() {
+    let mut graph: Graph<i32, ()> = Graph::new();
+
+    let (first, diff_1) = graph.add_vertex(1);
+    let (second, diff_2) = graph.add_vertex(2);
+    let (_, diff_3) = graph.add_edge(first, second, ()).unwrap();
+    let diff_4 = graph.remove_vertex(first).unwrap();
+
+    // the second vertex is past the end of the new graph
+    let mut other: Graph<i32, ()> = Graph::new();
+    other.apply_diff(diff_2.clone()).unwrap();
+    other.rollback_diff(diff_2.clone()).unwrap();
+
+    other
+        .apply_diffs(vec![diff_1, diff_2, diff_3, diff_4])
+        .unwrap();
+
+    assert!(other.get_vertex(first).is_none());
+    assert_eq!(*other.get_vertex_data(second).unwrap(), 2);
+
+    let (third, _) = other.add_vertex(3);
+    assert_eq!(third.0.index, first.0.index);
+}
+
+#[test]
+fn test_self_loop_add_remove() {
+    use crate::GraphDiff;
+
+    let mut graph: Graph<i32, i32> = Graph::new();
+
+    let (vertex, _) = graph.add_vertex(1);
+    let (edge, _) = graph.add_edge(vertex, vertex, 10).unwrap();
+
+    assert_eq!(graph[vertex].get_connections_from(), &vec![(vertex, edge)]);
+    assert_eq!(graph[vertex].get_connections_to(), &vec![(vertex, edge)]);
+    assert_eq!(graph[vertex].get_connected_edges(), vec![edge]);
+    assert_eq!(graph.shared_edges(vertex, vertex).unwrap().count(), 1);
+
+    let diff = graph.remove_edge(edge).unwrap();
+    assert!(matches!(diff, GraphDiff::RemoveEdge(diff) if *diff.get_edge().data() == 10));
+    assert!(graph[vertex].get_connections_from().is_empty());
+    assert!(graph[vertex].get_connections_to().is_empty());
+
+    let (edge, _) = graph.add_edge(vertex, vertex, 20).unwrap();
+    let diff = graph.remove_vertex(vertex).unwrap();
+    assert!(matches!(
+        diff,
+        GraphDiff::RemoveVertex(diff)
+            if *diff.get_vertex().data() == 1 && diff.get_removed_edges().len() == 1
+    ));
+    assert!(graph.get_edge(edge).is_none());
+    assert!(graph.get_edges().is_empty());
+}
+
+#[test]
+fn test_self_loop_diffs() {
+    let mut graph: Graph<i32, i32> = Graph::new();
+
+    let (vertex, diff_1) = graph.add_vertex(1);
+    let (other, diff_2) = graph.add_vertex(2);
+    let (self_loop, diff_3) = graph.add_edge(vertex, vertex, 10).unwrap();
+    let (edge, diff_4) = graph.add_edge(other, vertex, 20).unwrap();
+    let (_, diff_5) = graph.update_edge(self_loop, 11).unwrap();
+    let (_, diff_6) = graph.update_vertex(vertex, 3).unwrap();
+    let diff_7 = graph.remove_edge(self_loop).unwrap();
+    let (second_loop, diff_8) = graph.add_edge(vertex, vertex, 30).unwrap();
+    let diff_9 = graph.remove_vertex(vertex).unwrap();
+
+    let diffs = vec![
+        diff_1, diff_2, diff_3, diff_4, diff_5, diff_6, diff_7, diff_8, diff_9,
+    ];
+
+    assert!(graph.get_edges().is_empty());
+    assert!(graph[other].get_connections_to().is_empty());
+
+    // roll back one step at a time, checking the self-loop after each step
+    graph.rollback_diff(diffs[8].clone()).unwrap();
+    assert_eq!(*graph.get_vertex_data(vertex).unwrap(), 3);
+    assert_eq!(*graph.get_edge_data(second_loop).unwrap(), 30);
+    assert_eq!(graph[vertex].get_connected_edges().len(), 2);
+    assert_eq!(graph[other].get_connections_to(), &vec![(vertex, edge)]);
+
+    graph.rollback_diff(diffs[7].clone()).unwrap();
+    assert!(graph.get_edge(second_loop).is_none());
+    assert_eq!(graph[vertex].get_connected_edges(), vec![edge]);
+
+    graph.rollback_diff(diffs[6].clone()).unwrap();
+    assert_eq!(*graph.get_edge_data(self_loop).unwrap(), 11);
+    assert!(graph[vertex]
+        .get_connections_from()
+        .contains(&(vertex, self_loop)));
+    assert!(graph[vertex]
+        .get_connections_to()
+        .contains(&(vertex, self_loop)));
+
+    graph.rollback_diff(diffs[5].clone()).unwrap();
+    graph.rollback_diff(diffs[4].clone()).unwrap();
+    assert_eq!(*graph.get_vertex_data(vertex).unwrap(), 1);
+    assert_eq!(*graph.get_edge_data(self_loop).unwrap(), 10);
+
+    graph.rollback_diffs(diffs[..4].to_vec()).unwrap();
+    assert!(graph.get_verticies().is_empty());
+    assert!(graph.get_edges().is_empty());
+
+    // and forwards again
+    graph.apply_diffs(diffs[..8].to_vec()).unwrap();
+    assert_eq!(*graph.get_vertex_data(vertex).unwrap(), 3);
+    assert_eq!(*graph.get_edge_data(second_loop).unwrap(), 30);
+    assert!(graph.get_edge(self_loop).is_none());
+    assert_eq!(graph[vertex].get_connections_from().len(), 2);
+    assert_eq!(graph[vertex].get_connections_to().len(), 1);
+
+    graph.apply_diff(diffs[8].clone()).unwrap();
+    assert!(graph.get_vertex(vertex).is_none());
+    assert!(graph.get_edges().is_empty());
+    assert!(graph[other].get_connections_to().is_empty());
+
+    // rolling back the whole history leaves an empty graph
+    graph.rollback_diffs(diffs).unwrap();
+    assert!(graph.get_verticies().is_empty());
+}
+
+#[test]
+fn test_self_loop_rollback_added_vertex() {
+    let mut graph: Graph<(), ()> = Graph::new();
+
+    let (vertex, diff_1) = graph.add_vertex(());
+    graph.add_edge(vertex, vertex, ()).unwrap();
+
+    // rolling back the vertex also takes its self-loop with it
+    graph.rollback_diff(diff_1).unwrap();
+    assert!(graph.get_verticies().is_empty());
+    assert!(graph.get_edges().is_empty());
+}
+
+#[test]
+fn test_validate_and_repair() {
+    use crate::{ConnectionList, EdgeIndex, Inconsistency, SlotKind};
+
+    let mut graph: Graph<i32, i32> = Graph::new();
+
+    let (first, _) = graph.add_vertex(1);
+    let (second, _) = graph.add_vertex(2);
+    let (third, _) = graph.add_vertex(3);
+    let (first_edge, _) = graph.add_edge(first, second, 10).unwrap();
+    let (second_edge, _) = graph.add_edge(second, second, 20).unwrap();
+    let (third_edge, _) = graph.add_edge(second, third, 30).unwrap();
+    graph.remove_vertex(third).unwrap();
+
+    assert_eq!(graph.validate(), vec![]);
+
+    // break the graph in a few different ways
+    graph.verticies[first.0].connections_to.clear();
+    graph.verticies[second.0]
+        .connections_from
+        .push((second, second_edge));
+    graph.verticies[second.0].connections_to.push((
+        first,
+        EdgeIndex(Index {
+            index: 7,
+            generation: 0,
+        }),
+    ));
+    graph
+        .edges
+        .occupy_vacancy(third_edge.0, crate::Edge::new(second, third, 30))
+        .unwrap();
+    graph.verticies.next_open_slot = None;
+
+    let problems = graph.validate();
+
+    assert!(problems.contains(&Inconsistency::OpenSlotNotInFreeList {
+        kind: SlotKind::Vertex,
+        slot: third.0.index
+    }));
+    assert!(problems.contains(&Inconsistency::MissingConnection {
+        vertex: first,
+        edge: first_edge,
+        list: ConnectionList::To
+    }));
+    assert!(problems.contains(&Inconsistency::DuplicateConnection {
+        vertex: second,
+        edge: second_edge,
+        list: ConnectionList::From
+    }));
+    assert!(problems.contains(&Inconsistency::UnknownConnection {
+        vertex: second,
+        edge: EdgeIndex(Index {
+            index: 7,
+            generation: 0
+        }),
+        list: ConnectionList::To
+    }));
+    assert!(problems.contains(&Inconsistency::DanglingEndpoint {
+        edge: third_edge,
+        vertex: third
+    }));
+    assert!(problems.contains(&Inconsistency::MissingConnection {
+        vertex: second,
+        edge: third_edge,
+        list: ConnectionList::To
+    }));
+    assert_eq!(problems.len(), 6);
+
+    let removed = graph.repair();
+    assert_eq!(removed.len(), 1);
+    assert_eq!(removed[0].0, third_edge);
+    assert_eq!(graph.validate(), vec![]);
+
+    assert_eq!(
+        graph[first].get_connections_to(),
+        &vec![(second, first_edge)]
+    );
+    assert_eq!(graph[second].get_connected_edges().len(), 2);
+
+    // the repaired free list hands out the open slot again
+    let (fourth, _) = graph.add_vertex(4);
+    assert_eq!(fourth.0.index, third.0.index);
+}
+
+#[test]
+fn test_validate_free_list() {
+    use crate::{Inconsistency, SlotKind};
+
+    let mut graph: Graph<(), ()> = Graph::new();
+
+    let (first, _) = graph.add_vertex(());
+    let (second, _) = graph.add_vertex(());
+    graph.remove_vertex(first).unwrap();
+    graph.remove_vertex(second).unwrap();
+
+    assert_eq!(graph.validate(), vec![]);
+
+    // point the end of the free list back at its start
+    if let Element::Open { next, .. } = &mut graph.verticies.vec[first.0.index] {
+        *next = Some(second.0.index);
+    }
+
+    assert_eq!(
+        graph.validate(),
+        vec![Inconsistency::FreeListCycle {
+            kind: SlotKind::Vertex
+        }]
+    );
+
+    graph.repair();
+    assert_eq!(graph.validate(), vec![]);
+}
+
+#[test]
+fn test_graph_serde_rebuilds_connections() {
+    let mut graph: Graph<i32, i32> = Graph::new();
+
+    let (first, _) = graph.add_vertex(1);
+    let (second, _) = graph.add_vertex(2);
+    let (first_edge, _) = graph.add_edge(first, second, 10).unwrap();
+    let (second_edge, _) = graph.add_edge(second, second, 20).unwrap();
+
+    let serialized = serde_json::to_value(&graph).unwrap();
+    assert_eq!(
+        serialized,
+        json!({
+            "verticies": {
+                "0.0": { "data": 1 },
+                "1.0": { "data": 2 },
+            },
+            "edges": {
+                "0.0": { "from": "0.0", "to": "1.0", "data": 10 },
+                "1.0": { "from": "1.0", "to": "1.0", "data": 20 },
+            },
+        })
+    );
+
+    let deserialized: Graph<i32, i32> = serde_json::from_value(serialized).unwrap();
+    assert_eq!(deserialized.validate(), vec![]);
+    assert_eq!(
+        deserialized[first].get_connections_to(),
+        &vec![(second, first_edge)]
+    );
+    assert_eq!(
+        deserialized[second].get_connections_from(),
+        &vec![(first, first_edge), (second, second_edge)]
+    );
+    assert_eq!(
+        deserialized[second].get_connections_to(),
+        &vec![(second, second_edge)]
+    );
+}
+
+#[test]
+fn test_graph_serde_rejects_dangling_edges() {
+    let serialized = json!({
+        "verticies": {
+            "0.0": { "data": 1 },
+        },
+        "edges": {
+            "0.0": { "from": "0.0", "to": "1.0", "data": 10 },
+        },
+    });
+
+    let err = serde_json::from_value::<Graph<i32, i32>>(serialized).unwrap_err();
+    assert!(err.to_string().contains("does not exist"));
+}
+
+#[test]
+fn test_invalid_diff_errors() {
+    use crate::GraphError;
+
+    let mut graph: Graph<i32, i32> = Graph::new();
+
+    let (first, diff_1) = graph.add_vertex(1);
+    let (second, _) = graph.add_vertex(2);
+    let (third, _) = graph.add_vertex(3);
+    let (edge, diff_2) = graph.add_edge(first, second, 10).unwrap();
+
+    // the vertex is already there
+    assert!(matches!(
+        graph.apply_diff(diff_1.clone()),
+        Err(GraphError::VertexSlotOccupied { index }) if index == first
+    ));
+
+    // removing and re-adding moves the slot on a generation
+    let diff_3 = graph.remove_vertex(first).unwrap();
+    assert!(matches!(
+        graph.apply_diff(diff_1.clone()),
+        Err(GraphError::VertexGenerationMismatch { index, expected: 0, actual: 1 }) if index == first
+    ));
+
+    // the edge's endpoint is gone
+    graph.rollback_diff(diff_2.clone()).unwrap_err();
+    assert!(matches!(
+        graph.apply_diff(diff_2.clone()),
+        Err(GraphError::DanglingEndpoint { edge: e, vertex }) if e == edge && vertex == first
+    ));
+
+    graph.rollback_diff(diff_3).unwrap();
+
+    // an edge with the same index, but between different verticies
+    graph.rollback_diff(diff_2.clone()).unwrap();
+    let (other_edge, _) = graph.add_edge(second, third, 20).unwrap();
+    assert_eq!(other_edge, edge);
+    assert!(matches!(
+        graph.rollback_diff(diff_2.clone()),
+        Err(GraphError::EdgeEndpointsMismatch {
+            index,
+            expected_from,
+            actual_from,
+            ..
+        }) if index == edge && expected_from == first && actual_from == second
+    ));
+    assert!(matches!(
+        graph.apply_diff(diff_2),
+        Err(GraphError::EdgeSlotOccupied { index }) if index == edge
+    ));
+}
+
+#[test]
+fn test_rollback_remove_vertex_with_missing_neighbour() {
+    use crate::GraphError;
+
+    let mut graph: Graph<i32, i32> = Graph::new();
+
+    let (first, _) = graph.add_vertex(1);
+    let (second, _) = graph.add_vertex(2);
+    let (edge, _) = graph.add_edge(first, second, 10).unwrap();
+    let diff = graph.remove_vertex(first).unwrap();
+    graph.remove_vertex(second).unwrap();
+
+    // the removed edge has nowhere to connect to, so this fails cleanly
+    assert!(matches!(
+        graph.rollback_diff(diff),
+        Err(GraphError::DanglingEndpoint { edge: e, vertex }) if e == edge && vertex == second
+    ));
+    assert!(graph.get_verticies().is_empty());
+    assert_eq!(graph.validate(), vec![]);
+}
+
+#[test]
+fn test_invert_diff() {
+    use crate::GraphDiff;
+
+    let mut graph: Graph<i32, i32> = Graph::new();
+
+    let (first, diff_1) = graph.add_vertex(1);
+    let (second, diff_2) = graph.add_vertex(2);
+    let (edge, diff_3) = graph.add_edge(first, second, 10).unwrap();
+    let (_, diff_4) = graph.update_vertex(first, 5).unwrap();
+    let diff_5 = graph.remove_vertex(second).unwrap();
+
+    let log = [diff_1, diff_2, diff_3, diff_4, diff_5];
+
+    // undo everything by applying the inverted diffs
+    for diff in log.iter().rev() {
+        graph.apply_diff(diff.clone().invert()).unwrap();
+    }
+    assert!(graph.get_verticies().is_empty());
+    assert!(graph.get_edges().is_empty());
+    assert_eq!(graph.validate(), vec![]);
+
+    // rolling back the inverted diffs redoes everything
+    for diff in log.iter() {
+        graph.rollback_diff(diff.clone().invert()).unwrap();
+    }
+    assert_eq!(*graph.get_vertex_data(first).unwrap(), 5);
+    assert!(graph.get_vertex(second).is_none());
+    assert!(graph.get_edge(edge).is_none());
+
+    // adds and removes invert into each other
+    let [diff_1, _, diff_3, _, diff_5] = log;
+    assert!(matches!(diff_1.invert(), GraphDiff::RemoveVertex(_)));
+    assert!(matches!(diff_3.invert(), GraphDiff::RemoveEdge(_)));
+    assert!(matches!(
+        diff_5.invert(),
+        GraphDiff::Group(diffs) if matches!(
+            diffs.as_slice(),
+            [GraphDiff::AddVertex(_), GraphDiff::AddEdge(_)]
+        )
+    ));
+
+    // inverting twice gives back the original diff
+    let (_, update) = graph.update_vertex(first, 6).unwrap();
+    match update.clone().invert() {
+        GraphDiff::UpdateVertexData(inverted) => {
+            assert_eq!(*inverted.get_before(), 6);
+            assert_eq!(*inverted.get_after(), 5);
+        }
+        _ => panic!("expected an update"),
+    }
+    assert!(matches!(
+        update.invert().invert(),
+        GraphDiff::UpdateVertexData(diff) if *diff.get_after() == 6
+    ));
+}
+
+#[test]
+fn test_invert_group_diff() {
+    let mut graph: Graph<i32, i32> = Graph::new();
+
+    let (first, _) = graph.add_vertex(1);
+    let ((second, edge), diff) = graph
+        .transaction(|tx| {
+            let second = tx.add_vertex(2);
+            let edge = tx.add_edge(first, second, 10)?;
+            tx.update_vertex(first, 3)?;
+
+            Ok((second, edge))
+        })
+        .unwrap();
+
+    graph.apply_diff(diff.clone().invert()).unwrap();
+    assert_eq!(*graph.get_vertex_data(first).unwrap(), 1);
+    assert!(graph.get_vertex(second).is_none());
+    assert!(graph.get_edge(edge).is_none());
+
+    graph.apply_diff(diff.invert().invert()).unwrap();
+    assert_eq!(*graph.get_vertex_data(first).unwrap(), 3);
+    assert_eq!(*graph.get_edge_data(edge).unwrap(), 10);
+    assert_eq!(graph.validate(), vec![]);
+}
+
+#[test]
+fn test_apply_diff_ref() {
+    let mut graph: Graph<i32, i32> = Graph::new();
+
+    let (first, diff_1) = graph.add_vertex(1);
+    let (second, diff_2) = graph.add_vertex(2);
+    let (edge, diff_3) = graph.add_edge(first, second, 10).unwrap();
+    let (_, diff_4) = graph.update_edge(edge, 20).unwrap();
+    let diff_5 = graph.remove_vertex(first).unwrap();
+
+    let log = [diff_1, diff_2, diff_3, diff_4, diff_5];
+
+    for diff in log.iter().rev() {
+        graph.rollback_diff_ref(diff).unwrap();
+    }
+    assert!(graph.get_verticies().is_empty());
+
+    for diff in log.iter() {
+        graph.apply_diff_ref(diff).unwrap();
+    }
+    assert!(graph.get_vertex(first).is_none());
+    assert_eq!(*graph.get_vertex_data(second).unwrap(), 2);
+
+    // the log is still usable afterwards
+    graph.rollback_diff_ref(&log[4]).unwrap();
+    assert_eq!(*graph.get_edge_data(edge).unwrap(), 20);
+    assert_eq!(graph.validate(), vec![]);
+}
+
+#[test]
+fn test_coalesce_updates() {
+    use crate::{coalesce, GraphDiff};
+
+    let mut graph: Graph<i32, i32> = Graph::new();
+
+    let (first, _) = graph.add_vertex(0);
+    let (second, _) = graph.add_vertex(0);
+    let mut diffs = vec![];
+
+    // dragging both verticies at once
+    for i in 1..=10 {
+        diffs.push(graph.update_vertex(first, i).unwrap().1);
+        diffs.push(graph.update_vertex(second, -i).unwrap().1);
+    }
+
+    let coalesced = coalesce(diffs);
+    assert_eq!(coalesced.len(), 2);
+    assert!(matches!(
+        &coalesced[0],
+        GraphDiff::UpdateVertexData(diff) if *diff.get_before() == 0 && *diff.get_after() == 10
+    ));
+
+    graph.rollback_diffs(coalesced.clone()).unwrap();
+    assert_eq!(*graph.get_vertex_data(first).unwrap(), 0);
+    assert_eq!(*graph.get_vertex_data(second).unwrap(), 0);
+
+    graph.apply_diffs(coalesced).unwrap();
+    assert_eq!(*graph.get_vertex_data(first).unwrap(), 10);
+    assert_eq!(*graph.get_vertex_data(second).unwrap(), -10);
+}
+
+#[test]
+fn test_coalesce_cancels_add_and_remove() {
+    use crate::coalesce;
+
+    let mut graph: Graph<i32, i32> = Graph::new();
+
+    let (first, _) = graph.add_vertex(1);
+    let (_, diff) = graph
+        .transaction(|tx| {
+            let temporary = tx.add_vertex(2);
+            tx.add_edge(first, temporary, 10)?;
+            tx.update_vertex(temporary, 3)?;
+            tx.update_vertex(first, 4)?;
+            tx.remove_vertex(temporary)?;
+
+            Ok(())
+        })
+        .unwrap();
+
+    let coalesced = coalesce(vec![diff]);
+    assert_eq!(coalesced.len(), 1);
+
+    let mut replayed: Graph<i32, i32> = Graph::new();
+    let (_, add_first) = replayed.add_vertex(1);
+    replayed.apply_diffs(coalesced.clone()).unwrap();
+    assert_eq!(*replayed.get_vertex_data(first).unwrap(), 4);
+    assert_eq!(replayed.get_verticies().len(), 1);
+    assert!(replayed.get_edges().is_empty());
+
+    replayed.rollback_diffs(coalesced).unwrap();
+    assert_eq!(*replayed.get_vertex_data(first).unwrap(), 1);
+    replayed.rollback_diff(add_first).unwrap();
+    assert_eq!(replayed.validate(), vec![]);
+}
+
+#[test]
+fn test_coalesce_keeps_reused_slots() {
+    use crate::coalesce;
+
+    let mut graph: Graph<i32, i32> = Graph::new();
+
+    let (first, diff_1) = graph.add_vertex(1);
+    let diff_2 = graph.remove_vertex(first).unwrap();
+    let (second, diff_3) = graph.add_vertex(2);
+    assert_eq!(first.0.index, second.0.index);
+
+    // the second vertex relies on the slot's generation being bumped
+    let coalesced = coalesce(vec![diff_1, diff_2, diff_3]);
+    assert_eq!(coalesced.len(), 3);
+
+    let mut replayed: Graph<i32, i32> = Graph::new();
+    replayed.apply_diffs(coalesced).unwrap();
+    assert_eq!(*replayed.get_vertex_data(second).unwrap(), 2);
+}
+
+#[test]
+fn test_update_with() {
+    let mut graph: Graph<Vec<i32>, i32> = Graph::new();
+
+    let (first, _) = graph.add_vertex(vec![1]);
+    let (second, _) = graph.add_vertex(vec![]);
+    let (edge, _) = graph.add_edge(first, second, 10).unwrap();
+
+    let (len, vertex_diff) = graph
+        .update_vertex_with(first, |data| {
+            data.push(2);
+            data.len()
+        })
+        .unwrap();
+    assert_eq!(len, 2);
+    assert_eq!(*graph.get_vertex_data(first).unwrap(), vec![1, 2]);
+
+    let (_, edge_diff) = graph.update_edge_with(edge, |data| *data += 1).unwrap();
+    assert_eq!(*graph.get_edge_data(edge).unwrap(), 11);
+
+    graph.rollback_diff(edge_diff).unwrap();
+    graph.rollback_diff(vertex_diff.clone()).unwrap();
+    assert_eq!(*graph.get_vertex_data(first).unwrap(), vec![1]);
+    assert_eq!(*graph.get_edge_data(edge).unwrap(), 10);
+
+    graph.apply_diff(vertex_diff).unwrap();
+    assert_eq!(*graph.get_vertex_data(first).unwrap(), vec![1, 2]);
+
+    graph.remove_vertex(second).unwrap();
+    assert!(graph.update_edge_with(edge, |_| ()).is_err());
+}
+
+/// A big table where only a few entries change at a time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Params(Vec<i32>);
+
+/// `(position, before, after)` for every changed entry
+type ParamsPatch = Vec<(usize, i32, i32)>;
+
+impl crate::Patchable for Params {
+    type Patch = ParamsPatch;
+
+    fn make_patch(&self, after: &Self) -> Self::Patch {
+        self.0
+            .iter()
+            .zip(after.0.iter())
+            .enumerate()
+            .filter(|(_, (before, after))| before != after)
+            .map(|(position, (before, after))| (position, *before, *after))
+            .collect()
+    }
+}
+
+impl crate::Patch<Params> for ParamsPatch {
+    fn apply(&self, data: &mut Params) {
+        for (position, _, after) in self {
+            data.0[*position] = *after;
+        }
+    }
+
+    fn revert(&self, data: &mut Params) {
+        for (position, before, _) in self {
+            data.0[*position] = *before;
+        }
+    }
+}
+
+#[test]
+fn test_patch_vertex() {
+    let mut graph: Graph<Params, Params> = Graph::new();
+
+    let (first, _) = graph.add_vertex(Params(vec![0; 1000]));
+    let (second, _) = graph.add_vertex(Params(vec![]));
+    let (edge, _) = graph.add_edge(first, second, Params(vec![1, 2])).unwrap();
+
+    let diff_1 = graph.patch_vertex(first, vec![(10, 0, 5)]).unwrap();
+    let (_, diff_2) = graph
+        .patch_vertex_with(first, |params| params.0[20] = 7)
+        .unwrap();
+    let (_, diff_3) = graph
+        .patch_edge_with(edge, |params| params.0.swap(0, 1))
+        .unwrap();
+    assert_eq!(graph.get_vertex_data(first).unwrap().0[10], 5);
+    assert_eq!(graph.get_vertex_data(first).unwrap().0[20], 7);
+    assert_eq!(*graph.get_edge_data(edge).unwrap(), Params(vec![2, 1]));
+
+    // only the changed entries are stored
+    assert_eq!(
+        format!("{:?}", diff_3),
+        "PatchEdgeData(PatchEdgeData { index: EdgeIndex((0, 0)), patch: [(0, 1, 2), (1, 2, 1)], reverted: false })"
+    );
+
+    let log = [diff_1, diff_2, diff_3];
+    for diff in log.iter().rev() {
+        graph.rollback_diff_ref(diff).unwrap();
+    }
+    assert_eq!(
+        *graph.get_vertex_data(first).unwrap(),
+        Params(vec![0; 1000])
+    );
+    assert_eq!(*graph.get_edge_data(edge).unwrap(), Params(vec![1, 2]));
+
+    graph.apply_diffs(log.iter().cloned()).unwrap();
+    graph.apply_diff(log[1].clone().invert()).unwrap();
+    assert_eq!(graph.get_vertex_data(first).unwrap().0[10], 5);
+    assert_eq!(graph.get_vertex_data(first).unwrap().0[20], 0);
+
+    graph.remove_vertex(second).unwrap();
+    assert!(graph
+        .patch_vertex::<crate::NoPatch>(second, vec![])
+        .is_err());
+
+    // patches are serialized like any other diff
+    let json = serde_json::to_string(&log[2]).unwrap();
+    let diff: crate::GraphDiff<Params, Params, ParamsPatch, ParamsPatch> =
+        serde_json::from_str(&json).unwrap();
+    assert_eq!(format!("{:?}", diff), format!("{:?}", log[2]));
+
+    // and can be sent between threads
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<crate::History<Params, Params, (), ParamsPatch, ParamsPatch>>();
+}
+
+#[test]
+fn test_non_clone_data() {
+    /// Something like a handle to an audio buffer, which can't be copied
+    #[derive(Debug, PartialEq)]
+    struct Handle(u32);
+
+    let mut graph: Graph<Handle, Handle> = Graph::new();
+
+    let first = graph.add_vertex_untracked(Handle(1));
+    let second = graph.add_vertex_untracked(Handle(2));
+    let third = graph.add_vertex_untracked(Handle(3));
+    let edge = graph.add_edge_untracked(first, second, Handle(10)).unwrap();
+
+    assert_eq!(graph[first].data(), &Handle(1));
+    assert_eq!(graph.vertex_data_iter().count(), 3);
+    assert_eq!(graph.get_edge_data(edge), Some(&Handle(10)));
+
+    let diff_1 = graph.remove_vertex(first).unwrap();
+    let diff_2 = graph.remove_vertex(second).unwrap();
+    let diff_3 = graph.remove_vertex(third).unwrap();
+    assert!(graph.get_verticies().is_empty());
+
+    // the first vertex's edge needs the second one, so this fails part way
+    // through and puts the third vertex back the way it was
+    assert!(graph.rollback_diffs(vec![diff_2, diff_1, diff_3]).is_err());
+    assert!(graph.get_verticies().is_empty());
+    assert_eq!(graph.validate(), vec![]);
+
+    let diff_1 = {
+        let first = graph.add_vertex_untracked(Handle(1));
+        graph.remove_vertex(first).unwrap()
+    };
+    graph.rollback_diff(diff_1).unwrap();
+    assert_eq!(graph.vertex_data_iter().next().unwrap().1, &Handle(1));
+}
+
+#[test]
+fn test_describe_diff() {
+    use crate::GraphDiff;
+
+    let mut graph: Graph<i32, i32> = Graph::new();
+
+    let (first, add_first) = graph.add_vertex(1);
+    let (second, _) = graph.add_vertex(2);
+    let (edge, _) = graph.add_edge(first, second, 10).unwrap();
+    let (_, update) = graph.update_edge(edge, 20).unwrap();
+    let remove = graph.remove_vertex(second).unwrap();
+
+    assert_eq!(add_first.describe(), "added 1 vertex");
+    assert_eq!(update.describe(), "updated 1 edge");
+    assert_eq!(remove.describe(), "removed 1 vertex, removed 1 edge");
+    assert_eq!(remove.invert().describe(), "added 1 vertex, added 1 edge");
+    assert_eq!(
+        GraphDiff::<i32, i32>::Group(Vec::new()).describe(),
+        "no changes"
+    );
+}
+
+#[test]
+fn test_diff_against() {
+    use crate::GraphDiff;
+
+    let mut before: Graph<i32, i32> = Graph::new();
+    let first = before.add_vertex_untracked(1);
+    let second = before.add_vertex_untracked(2);
+    let third = before.add_vertex_untracked(3);
+    let kept_edge = before.add_edge_untracked(first, second, 10).unwrap();
+    before.add_edge_untracked(second, third, 20).unwrap();
+
+    let mut after = before.clone();
+    after.update_vertex(first, 5).unwrap();
+    after.update_edge(kept_edge, 15).unwrap();
+    after.remove_vertex(third).unwrap();
+    let (fourth, _) = after.add_vertex(4);
+    let (new_edge, _) = after.add_edge(fourth, first, 30).unwrap();
+
+    let diffs = before.diff_against(&after);
+    assert_eq!(
+        GraphDiff::Group(diffs.clone()).describe(),
+        "added 1 vertex, added 1 edge, removed 1 vertex, removed 1 edge, updated 1 vertex, updated 1 edge"
+    );
+    assert!(after.diff_against(&after).is_empty());
+
+    let mut graph = before.clone();
+    graph.apply_diffs(diffs.clone()).unwrap();
+    assert!(graph.diff_against(&after).is_empty());
+    assert_eq!(graph[new_edge].get_from(), fourth);
+
+    // turn it into undoable history
+    graph.rollback_diffs(diffs).unwrap();
+    assert!(graph.diff_against(&before).is_empty());
+}
+
+#[test]
+fn test_diff_against_generations() {
+    let mut before: Graph<i32, ()> = Graph::new();
+    let vertex = before.add_vertex_untracked(1);
+
+    // the slot is two generations ahead
+    let mut after = before.clone();
+    after.remove_vertex(vertex).unwrap();
+    let (reused, _) = after.add_vertex(2);
+    after.remove_vertex(reused).unwrap();
+    let (reused, _) = after.add_vertex(3);
+    assert_eq!(reused.0.index, vertex.0.index);
+
+    let mut graph = before.clone();
+    graph.apply_diffs(before.diff_against(&after)).unwrap();
+
+    let (index, data) = graph.vertex_data_iter().next().unwrap();
+    assert_eq!(*data, 3);
+    assert_eq!(index.0.index, reused.0.index);
+    assert_ne!(index, reused);
+}
@@ -0,0 +1,562 @@
+use alloc::{string::String, vec::Vec};
+use core::ops::Range;
+
+use crate::{
+    budget::{HistoryBudget, HistoryStats, RetentionPolicy, SizeEstimator},
+    coalesce::coalesce_with,
+    errors::GraphError,
+    graph::{Graph, GraphIndex},
+    graph_diff::GraphDiff,
+    patch::{NoPatch, Patch},
+    recorder::sealed::Record,
+    transaction::Transaction,
+    validate::SlotKind,
+};
+
+/// A labelled point in a [`History`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    label: String,
+    position: usize,
+}
+
+impl Checkpoint {
+    pub fn get_label(&self) -> &str {
+        &self.label
+    }
+
+    /// Number of entries applied at this point.
+    pub fn get_position(&self) -> usize {
+        self.position
+    }
+}
+
+/// A graph that records every change made to it, allowing changes to be
+/// undone and redone.
+///
+/// All changes have to go through the history, using [`crate::Recorder`]
+/// (there is no mutable access to the underlying graph), otherwise the
+/// recorded diffs would no longer line up with the state of the graph.
+///
+/// Each entry can carry metadata of type `M`, such as a label for an Edit
+/// menu or who made the change. `VP` and `EP` are the patch types of its
+/// entries, see [`GraphDiff`].
+#[derive(Debug, Clone)]
+pub struct History<V, E, M = (), VP = NoPatch, EP = NoPatch> {
+    graph: Graph<V, E>,
+    entries: Vec<GraphDiff<V, E, VP, EP>>,
+    /// Same length as `entries`
+    metadata: Vec<Option<M>>,
+    /// Number of entries that are currently applied to the graph. Everything
+    /// past this point is the redo branch.
+    position: usize,
+    checkpoints: Vec<Checkpoint>,
+    budget: Option<HistoryBudget>,
+    estimator: SizeEstimator<V, E>,
+    /// Estimated size of `entries`, kept up to date as they change.
+    bytes: usize,
+    dropped_entries: usize,
+    coalesced_entries: usize,
+}
+
+impl<V: Clone, E: Clone, M, VP: Patch<V>, EP: Patch<E>> History<V, E, M, VP, EP> {
+    pub fn new() -> History<V, E, M, VP, EP> {
+        History::from_graph(Graph::new())
+    }
+
+    /// Start recording history for an existing graph. The graph's current
+    /// state becomes the oldest state that can be undone to.
+    pub fn from_graph(graph: Graph<V, E>) -> History<V, E, M, VP, EP> {
+        History {
+            graph,
+            entries: Vec::new(),
+            metadata: Vec::new(),
+            position: 0,
+            checkpoints: Vec::new(),
+            budget: None,
+            estimator: SizeEstimator::default(),
+            bytes: 0,
+            dropped_entries: 0,
+            coalesced_entries: 0,
+        }
+    }
+
+    pub fn graph(&self) -> &Graph<V, E> {
+        &self.graph
+    }
+
+    pub fn into_graph(self) -> Graph<V, E> {
+        self.graph
+    }
+
+    /// Diffs that can currently be undone, oldest first.
+    pub fn undo_entries(&self) -> &[GraphDiff<V, E, VP, EP>] {
+        &self.entries[..self.position]
+    }
+
+    /// Diffs that can currently be redone, next redo first.
+    pub fn redo_entries(&self) -> &[GraphDiff<V, E, VP, EP>] {
+        &self.entries[self.position..]
+    }
+
+    /// Metadata of the entry that would be undone next.
+    pub fn undo_metadata(&self) -> Option<&M> {
+        self.position
+            .checked_sub(1)
+            .and_then(|index| self.entry_metadata(index))
+    }
+
+    /// Metadata of the entry that would be redone next.
+    pub fn redo_metadata(&self) -> Option<&M> {
+        self.entry_metadata(self.position)
+    }
+
+    /// Metadata of an entry, counting from the oldest of
+    /// [`History::undo_entries`] through to [`History::redo_entries`].
+    pub fn entry_metadata(&self, index: usize) -> Option<&M> {
+        self.metadata.get(index)?.as_ref()
+    }
+
+    /// Attach metadata to the most recent change, replacing any it already
+    /// had. Returns `NothingToUndo` if there are no changes.
+    pub fn set_metadata(&mut self, metadata: M) -> Result<Option<M>, GraphError> {
+        if !self.can_undo() {
+            return Err(GraphError::NothingToUndo);
+        }
+
+        Ok(self.metadata[self.position - 1].replace(metadata))
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.position > 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.position < self.entries.len()
+    }
+
+    /// Run `f` inside a transaction, recording all of its changes as a single
+    /// undoable entry. If `f` returns an error, its changes are rolled back
+    /// and nothing is recorded.
+    pub fn transaction<T, F>(&mut self, f: F) -> Result<T, GraphError>
+    where
+        F: FnOnce(&mut Transaction<'_, V, E, VP, EP>) -> Result<T, GraphError>,
+    {
+        self.transaction_entry(None, f)
+    }
+
+    /// Like [`History::transaction`], attaching `metadata` to the recorded
+    /// entry.
+    pub fn transaction_with_metadata<T, F>(&mut self, metadata: M, f: F) -> Result<T, GraphError>
+    where
+        F: FnOnce(&mut Transaction<'_, V, E, VP, EP>) -> Result<T, GraphError>,
+    {
+        self.transaction_entry(Some(metadata), f)
+    }
+
+    /// Undo the most recent change. Returns `NothingToUndo` if there is none.
+    pub fn undo(&mut self) -> Result<(), GraphError> {
+        if !self.can_undo() {
+            return Err(GraphError::NothingToUndo);
+        }
+
+        self.graph
+            .rollback_diff_ref(&self.entries[self.position - 1])?;
+        self.position -= 1;
+
+        Ok(())
+    }
+
+    /// Redo the most recently undone change. Returns `NothingToRedo` if there
+    /// is none.
+    pub fn redo(&mut self) -> Result<(), GraphError> {
+        if !self.can_redo() {
+            return Err(GraphError::NothingToRedo);
+        }
+
+        self.graph.apply_diff_ref(&self.entries[self.position])?;
+        self.position += 1;
+
+        Ok(())
+    }
+
+    /// Undo a single past entry, leaving the changes made after it in place,
+    /// e.g. to undo your own change in a shared session. This is recorded as a
+    /// new entry that reverts the old one, so it can be undone in turn.
+    ///
+    /// Fails with `UndoConflict` if later entries changed anything the entry
+    /// depends on (see [`History::undo_entry_conflicts`]), and
+    /// `EntryNotApplied` if the entry isn't one of [`History::undo_entries`].
+    pub fn undo_entry(&mut self, entry: usize) -> Result<(), GraphError> {
+        let conflicts = self.undo_entry_conflicts(entry)?;
+        if !conflicts.is_empty() {
+            return Err(GraphError::UndoConflict { entry, conflicts });
+        }
+
+        let revert = self.entries[entry].clone().invert();
+        self.graph.apply_diff_ref(&revert)?;
+        self.push(revert);
+
+        Ok(())
+    }
+
+    /// The vertices and edges that stop an entry from being undone with
+    /// [`History::undo_entry`]: anything it added, removed or updated that a
+    /// later entry has changed since, and endpoints of its edges that a later
+    /// entry has added or removed.
+    pub fn undo_entry_conflicts(&self, entry: usize) -> Result<Vec<GraphIndex>, GraphError> {
+        if entry >= self.position {
+            return Err(GraphError::EntryNotApplied { entry });
+        }
+
+        let mut conflicts = Vec::new();
+        for later in &self.entries[entry + 1..self.position] {
+            for index in self.entries[entry].conflicts_with(later) {
+                if !conflicts.contains(&index) {
+                    conflicts.push(index);
+                }
+            }
+        }
+
+        Ok(conflicts)
+    }
+
+    /// Merge every entry from `from` up to the current position into a single
+    /// entry, coalescing its diffs (see [`crate::coalesce`]). For continuous
+    /// edits like dragging, note `undo_entries().len()` when the gesture
+    /// starts, and coalesce from there when it ends.
+    ///
+    /// Panics if `from` is past the current position.
+    pub fn coalesce_entries(&mut self, from: usize) {
+        self.merge_entries(from, self.position);
+    }
+
+    /// Limit how many entries are kept, dropping or coalescing the oldest ones
+    /// whenever a change goes over budget. Applies straight away.
+    pub fn set_budget(&mut self, budget: Option<HistoryBudget>) {
+        self.budget = budget;
+        self.enforce_budget();
+    }
+
+    pub fn budget(&self) -> Option<HistoryBudget> {
+        self.budget
+    }
+
+    /// Set how the size of vertex and edge data is estimated, for
+    /// [`HistoryBudget::max_bytes`] and [`History::stats`].
+    pub fn set_size_estimator(&mut self, estimator: SizeEstimator<V, E>) {
+        self.estimator = estimator;
+        self.bytes = self.entries_size(0..self.entries.len());
+        self.enforce_budget();
+    }
+
+    pub fn stats(&self) -> HistoryStats {
+        HistoryStats {
+            undo_entries: self.position,
+            redo_entries: self.entries.len() - self.position,
+            bytes: self.bytes,
+            dropped_entries: self.dropped_entries,
+            coalesced_entries: self.coalesced_entries,
+        }
+    }
+
+    /// Forget all recorded changes, keeping the graph as it is. Checkpoints at
+    /// the current state are kept.
+    pub fn clear(&mut self) {
+        let position = self.position;
+        self.checkpoints
+            .retain(|checkpoint| checkpoint.position == position);
+        for checkpoint in self.checkpoints.iter_mut() {
+            checkpoint.position = 0;
+        }
+
+        self.entries.clear();
+        self.metadata.clear();
+        self.position = 0;
+        self.bytes = 0;
+    }
+
+    /// Label the current state, so it can be restored later. If the label is
+    /// already in use, it's moved here.
+    pub fn checkpoint(&mut self, label: impl Into<String>) {
+        let label = label.into();
+
+        self.checkpoints
+            .retain(|checkpoint| checkpoint.label != label);
+        self.checkpoints.push(Checkpoint {
+            label,
+            position: self.position,
+        });
+    }
+
+    /// Checkpoints that can still be restored, in the order they were made.
+    /// Checkpoints on a redo branch that's been discarded are forgotten.
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+
+    pub fn remove_checkpoint(&mut self, label: &str) -> Option<Checkpoint> {
+        let position = self.checkpoint_index(label)?;
+
+        Some(self.checkpoints.remove(position))
+    }
+
+    /// Undo or redo until the graph is in the state it was when the
+    /// checkpoint was made. If anything fails along the way, the graph is left
+    /// as it was.
+    pub fn restore(&mut self, label: &str) -> Result<(), GraphError> {
+        let target = self.checkpoint_position(label)?;
+        let start = self.position;
+
+        while self.position != target {
+            let result = if self.position > target {
+                self.undo()
+            } else {
+                self.redo()
+            };
+
+            if let Err(err) = result {
+                while self.position > start {
+                    self.undo()
+                        .expect("History has become corrupted while restoring a checkpoint");
+                }
+                while self.position < start {
+                    self.redo()
+                        .expect("History has become corrupted while restoring a checkpoint");
+                }
+
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The diffs that turn the state at checkpoint `from` into the state at
+    /// checkpoint `to`, coalesced to as few as possible (see
+    /// [`crate::coalesce`]). Works in either direction.
+    pub fn diff_between(
+        &self,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<GraphDiff<V, E, VP, EP>>, GraphError> {
+        let from = self.checkpoint_position(from)?;
+        let to = self.checkpoint_position(to)?;
+
+        Ok(self.diff_between_positions(from, to))
+    }
+
+    /// The diffs that turn the state at a checkpoint into the current state,
+    /// e.g. to show the changes since the last save. See
+    /// [`History::diff_between`].
+    pub fn diff_since(&self, label: &str) -> Result<Vec<GraphDiff<V, E, VP, EP>>, GraphError> {
+        let from = self.checkpoint_position(label)?;
+
+        Ok(self.diff_between_positions(from, self.position))
+    }
+
+    fn diff_between_positions(&self, from: usize, to: usize) -> Vec<GraphDiff<V, E, VP, EP>> {
+        let range = from.min(to)..from.max(to);
+        let reserved = self.reserved_slots(range.clone());
+
+        let diffs: Vec<GraphDiff<V, E, VP, EP>> = if from <= to {
+            self.entries[range].to_vec()
+        } else {
+            self.entries[range]
+                .iter()
+                .rev()
+                .map(|entry| entry.clone().invert())
+                .collect()
+        };
+
+        coalesce_with(diffs, |kind, slot| reserved.contains(&(kind, slot)))
+    }
+
+    /// Slots referred to by entries outside of `range`. A vertex or edge that
+    /// was added and removed again within `range` has still bumped its slot's
+    /// generation, so it can only be dropped when coalescing if no other entry
+    /// refers to that slot.
+    fn reserved_slots(&self, range: Range<usize>) -> Vec<(SlotKind, usize)> {
+        let mut reserved = Vec::new();
+
+        for (position, entry) in self.entries.iter().enumerate() {
+            if !range.contains(&position) {
+                entry.for_each_slot(&mut |kind, slot| reserved.push((kind, slot)));
+            }
+        }
+
+        reserved
+    }
+
+    /// Merge the entries in `from..to` (which can't be past the current
+    /// position) into one, see [`History::coalesce_entries`]. The merged entry
+    /// keeps the newest metadata.
+    fn merge_entries(&mut self, from: usize, to: usize) {
+        self.bytes -= self.entries_size(from..to);
+        let entries: Vec<GraphDiff<V, E, VP, EP>> = self.entries.drain(from..to).collect();
+        let metadata = self.metadata.drain(from..to).flatten().last();
+        self.position -= to - from;
+
+        let reserved = self.reserved_slots(from..from);
+        let mut diffs = coalesce_with(entries, |kind, slot| reserved.contains(&(kind, slot)));
+
+        let entry = match diffs.len() {
+            0 => None,
+            1 => diffs.pop(),
+            _ => Some(GraphDiff::Group(diffs)),
+        };
+
+        let merged = entry.is_some() as usize;
+        if let Some(entry) = entry {
+            self.bytes += self.estimator.diff_size(&entry);
+            self.entries.insert(from, entry);
+            self.metadata.insert(from, metadata);
+            self.position += 1;
+        }
+
+        // states in the middle of the merged entries no longer exist
+        self.checkpoints
+            .retain(|checkpoint| checkpoint.position <= from || checkpoint.position >= to);
+        for checkpoint in self.checkpoints.iter_mut() {
+            if checkpoint.position >= to && to > from {
+                checkpoint.position = checkpoint.position - (to - from) + merged;
+            }
+        }
+    }
+
+    fn entries_size(&self, range: Range<usize>) -> usize {
+        self.entries[range]
+            .iter()
+            .map(|entry| self.estimator.diff_size(entry))
+            .sum()
+    }
+
+    fn enforce_budget(&mut self) {
+        let budget = match self.budget {
+            Some(budget) => budget,
+            None => return,
+        };
+
+        let excess = self.excess_entries(&budget);
+        if excess == 0 {
+            return;
+        }
+
+        if budget.policy == RetentionPolicy::Coalesce {
+            // merging one more than the excess leaves the oldest state reachable
+            let to = (excess + 1).min(self.position);
+            let before = self.entries.len();
+            self.merge_entries(0, to);
+            self.coalesced_entries += before - self.entries.len();
+        }
+
+        // coalescing might not have saved enough space
+        let excess = self.excess_entries(&budget);
+        for _ in 0..excess {
+            self.drop_oldest();
+        }
+    }
+
+    /// How many of the oldest entries have to go to get within budget. Only
+    /// entries that can be undone are counted.
+    fn excess_entries(&self, budget: &HistoryBudget) -> usize {
+        let mut entries = self.entries.len();
+        let mut bytes = self.bytes;
+
+        let within_budget = |entries: usize, bytes: usize| {
+            budget.max_entries.map_or(true, |max| entries <= max)
+                && budget.max_bytes.map_or(true, |max| bytes <= max)
+        };
+
+        let mut excess = 0;
+        while excess < self.position && !within_budget(entries, bytes) {
+            if budget.max_bytes.is_some() {
+                bytes -= self.entries_size(excess..excess + 1);
+            }
+            entries -= 1;
+            excess += 1;
+        }
+
+        excess
+    }
+
+    fn drop_oldest(&mut self) {
+        self.bytes -= self.entries_size(0..1);
+        self.entries.remove(0);
+        self.metadata.remove(0);
+        self.position -= 1;
+        self.dropped_entries += 1;
+
+        // the state before the dropped entry can't be reached anymore
+        self.checkpoints
+            .retain(|checkpoint| checkpoint.position > 0);
+        for checkpoint in self.checkpoints.iter_mut() {
+            checkpoint.position -= 1;
+        }
+    }
+
+    fn checkpoint_index(&self, label: &str) -> Option<usize> {
+        self.checkpoints
+            .iter()
+            .position(|checkpoint| checkpoint.label == label)
+    }
+
+    fn checkpoint_position(&self, label: &str) -> Result<usize, GraphError> {
+        self.checkpoint_index(label)
+            .map(|index| self.checkpoints[index].position)
+            .ok_or_else(|| GraphError::CheckpointDoesNotExist {
+                label: label.into(),
+            })
+    }
+
+    fn transaction_entry<T, F>(&mut self, metadata: Option<M>, f: F) -> Result<T, GraphError>
+    where
+        F: FnOnce(&mut Transaction<'_, V, E, VP, EP>) -> Result<T, GraphError>,
+    {
+        let (value, diff) = self.graph.patched_transaction(f)?;
+
+        if !matches!(&diff, GraphDiff::Group(diffs) if diffs.is_empty()) {
+            self.push_entry(diff, metadata);
+        }
+
+        Ok(value)
+    }
+
+    fn push(&mut self, diff: GraphDiff<V, E, VP, EP>) {
+        self.push_entry(diff, None);
+    }
+
+    fn push_entry(&mut self, diff: GraphDiff<V, E, VP, EP>, metadata: Option<M>) {
+        // a new edit after undoing makes the redo branch unreachable
+        self.bytes -= self.entries_size(self.position..self.entries.len());
+        self.entries.truncate(self.position);
+        self.metadata.truncate(self.position);
+        let position = self.position;
+        self.checkpoints
+            .retain(|checkpoint| checkpoint.position <= position);
+        self.bytes += self.estimator.diff_size(&diff);
+        self.entries.push(diff);
+        self.metadata.push(metadata);
+        self.position += 1;
+
+        self.enforce_budget();
+    }
+}
+
+impl<V: Clone, E: Clone, M, VP: Patch<V>, EP: Patch<E>> Record<V, E, VP, EP>
+    for History<V, E, M, VP, EP>
+{
+    fn recorded_graph(&mut self) -> &mut Graph<V, E> {
+        &mut self.graph
+    }
+
+    fn record(&mut self, diff: GraphDiff<V, E, VP, EP>) {
+        self.push(diff);
+    }
+}
+
+impl<V: Clone, E: Clone, M, VP: Patch<V>, EP: Patch<E>> Default for History<V, E, M, VP, EP> {
+    fn default() -> Self {
+        History::new()
+    }
+}
@@ -0,0 +1,532 @@
+use crate::{
+    GraphDiff, GraphError, GraphIndex, History, HistoryBudget, Recorder, RetentionPolicy,
+    SizeEstimator,
+};
+
+#[test]
+fn test_history_undo_redo() {
+    let mut history: History<String, String> = History::new();
+
+    let first = history.add_vertex("first".into());
+    let second = history.add_vertex("second".into());
+    let edge = history.add_edge(first, second, "edge".into()).unwrap();
+    history
+        .update_vertex(first, "first_modified".into())
+        .unwrap();
+
+    assert!(history.can_undo());
+    assert!(!history.can_redo());
+
+    history.undo().unwrap();
+    assert_eq!(history.graph().get_vertex_data(first).unwrap(), "first");
+
+    history.undo().unwrap();
+    assert!(history.graph().get_edge(edge).is_none());
+
+    history.undo().unwrap();
+    history.undo().unwrap();
+    assert!(history.graph().get_vertex(first).is_none());
+    assert!(!history.can_undo());
+    assert!(matches!(history.undo(), Err(GraphError::NothingToUndo)));
+
+    history.redo().unwrap();
+    history.redo().unwrap();
+    history.redo().unwrap();
+    history.redo().unwrap();
+    assert!(!history.can_redo());
+    assert!(matches!(history.redo(), Err(GraphError::NothingToRedo)));
+
+    assert_eq!(
+        history.graph().get_vertex_data(first).unwrap(),
+        "first_modified"
+    );
+    assert_eq!(history.graph().get_edge_data(edge).unwrap(), "edge");
+}
+
+#[test]
+fn test_history_discards_redo_branch() {
+    let mut history: History<i32, ()> = History::new();
+
+    let first = history.add_vertex(1);
+    history.remove_vertex(first).unwrap();
+
+    history.undo().unwrap();
+    assert!(history.can_redo());
+
+    // editing after an undo throws away the old redo branch
+    history.update_vertex(first, 2).unwrap();
+    assert!(!history.can_redo());
+    assert_eq!(history.undo_entries().len(), 2);
+
+    history.undo().unwrap();
+    assert_eq!(*history.graph().get_vertex_data(first).unwrap(), 1);
+    history.undo().unwrap();
+    assert!(history.graph().get_vertex(first).is_none());
+
+    history.redo().unwrap();
+    history.redo().unwrap();
+    assert_eq!(*history.graph().get_vertex_data(first).unwrap(), 2);
+}
+
+#[test]
+fn test_history_transaction() {
+    let mut history: History<i32, ()> = History::new();
+
+    let first = history.add_vertex(1);
+    let second = history
+        .transaction(|tx| {
+            let second = tx.add_vertex(2);
+            tx.add_edge(first, second, ())?;
+            tx.update_vertex(first, 10)?;
+
+            Ok(second)
+        })
+        .unwrap();
+
+    assert_eq!(history.undo_entries().len(), 2);
+
+    history.undo().unwrap();
+    assert!(history.graph().get_vertex(second).is_none());
+    assert_eq!(*history.graph().get_vertex_data(first).unwrap(), 1);
+
+    history.redo().unwrap();
+    assert_eq!(*history.graph().get_vertex_data(first).unwrap(), 10);
+    assert_eq!(history.graph().get_edges().len(), 1);
+
+    // failed transactions aren't recorded
+    history
+        .transaction(|tx| {
+            tx.remove_vertex(first)
+                .and_then(|_| tx.remove_vertex(first))
+        })
+        .unwrap_err();
+    assert_eq!(history.undo_entries().len(), 2);
+    history.graph().get_vertex(first).unwrap();
+}
+
+#[test]
+fn test_history_coalesce_entries() {
+    let mut history: History<i32, ()> = History::new();
+
+    let first = history.add_vertex(0);
+    let start = history.undo_entries().len();
+
+    for i in 1..=100 {
+        history.update_vertex(first, i).unwrap();
+    }
+    let temporary = history.add_vertex(-1);
+    history.remove_vertex(temporary).unwrap();
+
+    history.coalesce_entries(start);
+    assert_eq!(history.undo_entries().len(), 2);
+
+    history.undo().unwrap();
+    assert_eq!(*history.graph().get_vertex_data(first).unwrap(), 0);
+    history.redo().unwrap();
+    assert_eq!(*history.graph().get_vertex_data(first).unwrap(), 100);
+
+    // a slot that another entry refers to isn't cancelled out
+    let second = history.add_vertex(1);
+    history.remove_vertex(second).unwrap();
+    let start = history.undo_entries().len();
+    let third = history.add_vertex(2);
+    assert_eq!(second.0.index, third.0.index);
+    history.remove_vertex(third).unwrap();
+    history.coalesce_entries(start);
+    assert_eq!(history.undo_entries().len(), 5);
+    history.undo().unwrap();
+    history.undo().unwrap();
+    history.undo().unwrap();
+    assert_eq!(history.graph().get_verticies().len(), 1);
+
+    // nothing left after coalescing means no entry at all
+    let start = history.undo_entries().len();
+    let fourth = history.add_vertex(3);
+    history.remove_vertex(fourth).unwrap();
+    history.coalesce_entries(start);
+    assert_eq!(history.undo_entries().len(), 2);
+    assert!(!history.can_redo());
+}
+
+#[test]
+fn test_history_update_with() {
+    let mut history: History<i32, i32> = History::new();
+
+    let first = history.add_vertex(1);
+    let second = history.add_vertex(2);
+    let edge = history.add_edge(first, second, 10).unwrap();
+
+    history
+        .update_vertex_with(first, |data| *data *= 5)
+        .unwrap();
+    history
+        .transaction(|tx| {
+            tx.update_edge_with(edge, |data| *data += 1)?;
+            tx.update_vertex_with(second, |data| *data += 1)
+        })
+        .unwrap();
+
+    history.undo().unwrap();
+    assert_eq!(*history.graph().get_edge_data(edge).unwrap(), 10);
+    assert_eq!(*history.graph().get_vertex_data(second).unwrap(), 2);
+    history.undo().unwrap();
+    assert_eq!(*history.graph().get_vertex_data(first).unwrap(), 1);
+
+    history.redo().unwrap();
+    assert_eq!(*history.graph().get_vertex_data(first).unwrap(), 5);
+}
+
+#[test]
+fn test_history_checkpoints() {
+    let mut history: History<i32, i32> = History::new();
+
+    let first = history.add_vertex(1);
+    history.checkpoint("before import");
+
+    let second = history.add_vertex(2);
+    let edge = history.add_edge(first, second, 10).unwrap();
+    history.update_vertex(first, 5).unwrap();
+    history.checkpoint("autosave");
+
+    history.remove_vertex(second).unwrap();
+
+    history.restore("before import").unwrap();
+    assert_eq!(*history.graph().get_vertex_data(first).unwrap(), 1);
+    assert!(history.graph().get_vertex(second).is_none());
+
+    history.restore("autosave").unwrap();
+    assert_eq!(*history.graph().get_vertex_data(first).unwrap(), 5);
+    assert_eq!(*history.graph().get_edge_data(edge).unwrap(), 10);
+    assert!(history.can_redo());
+
+    assert!(matches!(
+        history.restore("missing"),
+        Err(GraphError::CheckpointDoesNotExist { label }) if label == "missing"
+    ));
+
+    // editing discards checkpoints on the old redo branch
+    history.restore("before import").unwrap();
+    history.checkpoint("later");
+    history.redo().unwrap();
+    history.redo().unwrap();
+    history.checkpoint("on redo branch");
+    history.undo().unwrap();
+    history.update_vertex(first, 7).unwrap();
+
+    let labels: Vec<&str> = history
+        .checkpoints()
+        .iter()
+        .map(|checkpoint| checkpoint.get_label())
+        .collect();
+    assert_eq!(labels, vec!["before import", "later"]);
+}
+
+#[test]
+fn test_history_diff_between_checkpoints() {
+    let mut history: History<i32, i32> = History::new();
+
+    let first = history.add_vertex(1);
+    history.checkpoint("saved");
+
+    for i in 2..10 {
+        history.update_vertex(first, i).unwrap();
+    }
+    let temporary = history.add_vertex(100);
+    history.remove_vertex(temporary).unwrap();
+
+    // the updates are merged, and the temporary vertex cancels out
+    let changes = history.diff_since("saved").unwrap();
+    assert_eq!(changes.len(), 1);
+    assert!(matches!(
+        &changes[0],
+        GraphDiff::UpdateVertexData(diff) if *diff.get_before() == 1 && *diff.get_after() == 9
+    ));
+
+    history.checkpoint("now");
+    // going backwards, the temporary vertex is re-added and removed again,
+    // which cancels out just the same
+    let backwards = history.diff_between("now", "saved").unwrap();
+    assert_eq!(backwards.len(), 1);
+
+    let mut graph = history.graph().clone();
+    graph.apply_diffs(backwards).unwrap();
+    assert_eq!(*graph.get_vertex_data(first).unwrap(), 1);
+
+    // checkpoints inside coalesced entries are gone, later ones move
+    history.undo().unwrap();
+    history.checkpoint("middle");
+    history.redo().unwrap();
+    history.checkpoint("end");
+    history.coalesce_entries(1);
+    assert!(history.diff_since("middle").is_err());
+    assert!(history.diff_since("end").unwrap().is_empty());
+    assert_eq!(history.diff_between("saved", "end").unwrap().len(), 1);
+}
+
+#[test]
+fn test_history_drop_oldest_budget() {
+    let mut history: History<i32, i32> = History::new();
+
+    let first = history.add_vertex(0);
+    history.checkpoint("start");
+    for i in 1..10 {
+        history.update_vertex(first, i).unwrap();
+    }
+
+    history.set_budget(Some(HistoryBudget {
+        max_entries: Some(3),
+        max_bytes: None,
+        policy: RetentionPolicy::DropOldest,
+    }));
+
+    let stats = history.stats();
+    assert_eq!(stats.undo_entries, 3);
+    assert_eq!(stats.dropped_entries, 7);
+    assert!(history.checkpoints().is_empty());
+
+    while history.can_undo() {
+        history.undo().unwrap();
+    }
+    assert_eq!(*history.graph().get_vertex_data(first).unwrap(), 6);
+
+    // redo entries are never dropped, but get discarded by the next edit
+    history.redo().unwrap();
+    history.update_vertex(first, 100).unwrap();
+    assert_eq!(history.stats().undo_entries, 2);
+    assert_eq!(history.stats().redo_entries, 0);
+}
+
+#[test]
+fn test_history_coalesce_budget() {
+    let mut history: History<i32, i32> = History::new();
+    history.set_budget(Some(HistoryBudget {
+        max_entries: Some(3),
+        max_bytes: None,
+        policy: RetentionPolicy::Coalesce,
+    }));
+
+    let first = history.add_vertex(0);
+    for i in 1..10 {
+        history.update_vertex(first, i).unwrap();
+    }
+
+    let stats = history.stats();
+    assert_eq!(stats.undo_entries, 3);
+    assert_eq!(stats.coalesced_entries, 7);
+    assert_eq!(stats.dropped_entries, 0);
+
+    // the oldest state is still reachable
+    while history.can_undo() {
+        history.undo().unwrap();
+    }
+    assert!(history.graph().get_vertex(first).is_none());
+}
+
+#[test]
+fn test_history_byte_budget() {
+    let mut history: History<Vec<u8>, ()> = History::new();
+    history.set_size_estimator(SizeEstimator {
+        vertex: |data| data.len(),
+        edge: |_| 0,
+    });
+
+    let small = history.add_vertex(vec![0; 10]);
+    let entry_size = history.stats().bytes;
+
+    history.set_budget(Some(HistoryBudget {
+        max_entries: None,
+        max_bytes: Some(entry_size * 3),
+        policy: RetentionPolicy::Coalesce,
+    }));
+
+    // too big to coalesce into anything that fits
+    let big = history.add_vertex(vec![0; 1000]);
+    history.remove_vertex(big).unwrap();
+    assert_eq!(history.stats().undo_entries, 0);
+    assert!(history.stats().bytes <= entry_size * 3);
+
+    history.update_vertex(small, vec![1; 10]).unwrap();
+    assert_eq!(history.stats().undo_entries, 1);
+
+    history.undo().unwrap();
+    assert_eq!(
+        history.graph().get_vertex_data(small).unwrap(),
+        &vec![0; 10]
+    );
+}
+
+#[test]
+fn test_history_size_estimate_below_size_of() {
+    let mut history: History<Vec<u8>, Vec<u8>> = History::new();
+    // only counting heap bytes, which is less than `size_of` for empty data
+    history.set_size_estimator(SizeEstimator {
+        vertex: |data| data.len(),
+        edge: |data| data.len(),
+    });
+
+    let first = history.add_vertex(Vec::new());
+    let second = history.add_vertex(Vec::new());
+    history.add_edge(first, second, Vec::new()).unwrap();
+    history.remove_vertex(first).unwrap();
+
+    assert!(history.stats().bytes > 0);
+}
+
+#[test]
+fn test_history_diff_sizes() {
+    use core::mem::size_of;
+
+    let mut history: History<Vec<u8>, Vec<u8>> = History::new();
+    history.set_size_estimator(SizeEstimator {
+        vertex: |data| size_of::<Vec<u8>>() + data.len(),
+        edge: |data| size_of::<Vec<u8>>() + data.len(),
+    });
+    let diff_size = size_of::<GraphDiff<Vec<u8>, Vec<u8>>>();
+
+    let first = history.add_vertex(vec![0; 100]);
+    assert_eq!(history.stats().bytes, diff_size + 100);
+
+    // removing it again owns exactly the same data
+    history.remove_vertex(first).unwrap();
+    assert_eq!(history.stats().bytes, 2 * (diff_size + 100));
+
+    let first = history.add_vertex(vec![0; 100]);
+    let second = history.add_vertex(Vec::new());
+    history.add_edge(first, second, vec![0; 50]).unwrap();
+    let before = history.stats().bytes;
+
+    // plus the removed edge, which lives on the heap
+    history.remove_vertex(first).unwrap();
+    assert_eq!(
+        history.stats().bytes - before,
+        diff_size + 100 + size_of::<crate::graph_diff::RemoveEdge<Vec<u8>>>() + 50
+    );
+}
+
+#[test]
+fn test_history_bytes_stay_in_sync() {
+    let mut history: History<Vec<u8>, ()> = History::new();
+    let estimator = SizeEstimator {
+        vertex: |data: &Vec<u8>| 24 + data.len(),
+        edge: |_: &()| 0,
+    };
+    history.set_size_estimator(estimator);
+    let recount = |history: &History<Vec<u8>, ()>| -> usize {
+        let entries = history.undo_entries().iter();
+        entries
+            .chain(history.redo_entries())
+            .map(|entry| estimator.diff_size(entry))
+            .sum()
+    };
+
+    let first = history.add_vertex(vec![0; 10]);
+    let second = history.add_vertex(vec![0; 20]);
+    history.update_vertex(first, vec![0; 30]).unwrap();
+    history.undo().unwrap();
+    history.remove_vertex(second).unwrap();
+    assert_eq!(history.stats().bytes, recount(&history));
+
+    history.coalesce_entries(1);
+    assert_eq!(history.stats().bytes, recount(&history));
+
+    history.set_budget(Some(HistoryBudget {
+        max_entries: Some(1),
+        max_bytes: None,
+        policy: RetentionPolicy::DropOldest,
+    }));
+    assert_eq!(history.stats().bytes, recount(&history));
+
+    history.clear();
+    assert_eq!(history.stats().bytes, 0);
+}
+
+#[test]
+fn test_history_metadata() {
+    let mut history: History<i32, i32, &str> = History::new();
+
+    let first = history.add_vertex(1);
+    assert_eq!(history.undo_metadata(), None);
+    assert_eq!(history.set_metadata("Add Node").unwrap(), None);
+
+    history
+        .transaction_with_metadata("Delete 2 Nodes", |transaction| {
+            let second = transaction.add_vertex(2);
+            transaction.add_edge(first, second, 10)?;
+            transaction.remove_vertex(second)?;
+            transaction.remove_vertex(first)?;
+
+            Ok(())
+        })
+        .unwrap();
+
+    assert_eq!(history.undo_metadata(), Some(&"Delete 2 Nodes"));
+    assert_eq!(
+        history.undo_entries()[1].describe(),
+        "added 1 vertex, added 1 edge, removed 2 vertices, removed 1 edge"
+    );
+
+    history.undo().unwrap();
+    assert_eq!(history.undo_metadata(), Some(&"Add Node"));
+    assert_eq!(history.redo_metadata(), Some(&"Delete 2 Nodes"));
+
+    // merged entries keep the newest metadata
+    history.update_vertex(first, 5).unwrap();
+    history.coalesce_entries(0);
+    assert_eq!(history.entry_metadata(0), Some(&"Add Node"));
+    assert_eq!(
+        history.undo_entries()[0].describe(),
+        "added 1 vertex, updated 1 vertex"
+    );
+
+    history.undo().unwrap();
+    assert!(matches!(
+        history.set_metadata("nothing"),
+        Err(GraphError::NothingToUndo)
+    ));
+}
+
+#[test]
+fn test_history_undo_entry() {
+    let mut history: History<i32, i32> = History::new();
+
+    let mine = history.add_vertex(1);
+    let theirs = history.add_vertex(2);
+    history.update_vertex(mine, 5).unwrap();
+    let edge = history.add_edge(mine, theirs, 10).unwrap();
+    history.update_vertex(theirs, 20).unwrap();
+
+    // connecting an edge doesn't stop the update from being undone
+    history.undo_entry(2).unwrap();
+    assert_eq!(*history.graph().get_vertex_data(mine).unwrap(), 1);
+    assert_eq!(*history.graph().get_vertex_data(theirs).unwrap(), 20);
+    assert_eq!(history.undo_entries().len(), 6);
+
+    // the selective undo can be undone like anything else
+    history.undo().unwrap();
+    assert_eq!(*history.graph().get_vertex_data(mine).unwrap(), 5);
+    history.redo().unwrap();
+
+    assert!(matches!(
+        history.undo_entry(1),
+        Err(GraphError::UndoConflict { entry: 1, conflicts })
+            if conflicts == vec![GraphIndex::Vertex(theirs)]
+    ));
+    assert_eq!(
+        history.undo_entry_conflicts(0).unwrap(),
+        vec![GraphIndex::Vertex(mine)]
+    );
+
+    history.undo_entry(3).unwrap();
+    assert!(history.graph().get_edge(edge).is_none());
+    // it's already been undone
+    assert_eq!(
+        history.undo_entry_conflicts(3).unwrap(),
+        vec![GraphIndex::Edge(edge)]
+    );
+
+    history.undo().unwrap();
+    assert!(matches!(
+        history.undo_entry(6),
+        Err(GraphError::EntryNotApplied { entry: 6 })
+    ));
+}
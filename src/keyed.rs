@@ -0,0 +1,495 @@
+use alloc::vec::Vec;
+use core::{fmt::Debug, hash::Hash};
+
+use hashbrown::HashMap;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::KeyedError,
+    graph::{EdgeIndex, Graph, VertexIndex},
+    graph_diff::{GraphDiff, SlotAccess},
+    patch::Patch,
+};
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct KeyedEdge<K, E> {
+    pub key: K,
+    pub from: K,
+    pub to: K,
+    pub data: E,
+}
+
+/// A change to a [`KeyedGraph`] that refers to everything by key instead of
+/// by index, so it can be replayed into a graph with a different slot layout
+/// (another document, or another machine).
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "js_names", serde(tag = "variant", content = "data"))]
+pub enum KeyedDiff<K, V, E> {
+    AddVertex {
+        key: K,
+        data: V,
+    },
+    AddEdge(KeyedEdge<K, E>),
+    /// `edges` are the edges that were connected to the vertex, which are
+    /// removed with it.
+    RemoveVertex {
+        key: K,
+        data: V,
+        edges: Vec<KeyedEdge<K, E>>,
+    },
+    RemoveEdge(KeyedEdge<K, E>),
+    UpdateVertexData {
+        key: K,
+        before: V,
+        after: V,
+    },
+    UpdateEdgeData {
+        key: K,
+        before: E,
+        after: E,
+    },
+    Group(Vec<KeyedDiff<K, V, E>>),
+}
+
+/// A [`Graph`] where every vertex and edge also has a key chosen by the user
+/// (a UUID, say), which stays the same no matter which slot it ends up in.
+///
+/// Finding the index for a key, or the key for an index, is `O(1)`.
+///
+/// Vertices and edges are added and removed with [`KeyedDiff`]s, which carry
+/// their keys. A plain [`GraphDiff`] has no keys to give anything it adds, so
+/// only diffs that change data can be applied with
+/// [`KeyedGraph::apply_graph_diff`].
+#[derive(Debug, Clone)]
+pub struct KeyedGraph<K, V, E> {
+    graph: Graph<V, E>,
+    vertex_indexes: HashMap<K, VertexIndex>,
+    edge_indexes: HashMap<K, EdgeIndex>,
+    /// Key of whatever is in each slot, by slot
+    vertex_keys: Vec<Option<K>>,
+    edge_keys: Vec<Option<K>>,
+}
+
+/// A change to the key maps, so they can be put back if applying a diff
+/// fails halfway.
+enum Link<K> {
+    Vertex(K),
+    Edge(K),
+    UnlinkVertex(K, VertexIndex),
+    UnlinkEdge(K, EdgeIndex),
+}
+
+impl<K: Hash + Eq + Clone + Debug, V: Clone, E: Clone> KeyedGraph<K, V, E> {
+    pub fn new() -> KeyedGraph<K, V, E> {
+        KeyedGraph {
+            graph: Graph::new(),
+            vertex_indexes: HashMap::new(),
+            edge_indexes: HashMap::new(),
+            vertex_keys: Vec::new(),
+            edge_keys: Vec::new(),
+        }
+    }
+
+    pub fn graph(&self) -> &Graph<V, E> {
+        &self.graph
+    }
+
+    pub fn into_graph(self) -> Graph<V, E> {
+        self.graph
+    }
+
+    pub fn get_vertex_index(&self, key: &K) -> Option<VertexIndex> {
+        self.vertex_indexes.get(key).copied()
+    }
+
+    pub fn get_edge_index(&self, key: &K) -> Option<EdgeIndex> {
+        self.edge_indexes.get(key).copied()
+    }
+
+    pub fn get_vertex_key(&self, index: VertexIndex) -> Option<&K> {
+        self.graph.get_vertex(index)?;
+
+        self.vertex_keys.get(index.0.index)?.as_ref()
+    }
+
+    pub fn get_edge_key(&self, index: EdgeIndex) -> Option<&K> {
+        self.graph.get_edge(index)?;
+
+        self.edge_keys.get(index.0.index)?.as_ref()
+    }
+
+    /// Fails with `KeyInUse` if a vertex with this key already exists.
+    #[allow(clippy::type_complexity)]
+    pub fn add_vertex(
+        &mut self,
+        key: K,
+        data: V,
+    ) -> Result<(VertexIndex, KeyedDiff<K, V, E>), KeyedError<K>> {
+        let diff = KeyedDiff::AddVertex {
+            key: key.clone(),
+            data,
+        };
+        self.apply_diff(&diff)?;
+
+        Ok((self.vertex_indexes[&key], diff))
+    }
+
+    /// Fails with `KeyInUse` if an edge with this key already exists.
+    #[allow(clippy::type_complexity)]
+    pub fn add_edge(
+        &mut self,
+        key: K,
+        from: K,
+        to: K,
+        data: E,
+    ) -> Result<(EdgeIndex, KeyedDiff<K, V, E>), KeyedError<K>> {
+        let diff = KeyedDiff::AddEdge(KeyedEdge {
+            key: key.clone(),
+            from,
+            to,
+            data,
+        });
+        self.apply_diff(&diff)?;
+
+        Ok((self.edge_indexes[&key], diff))
+    }
+
+    pub fn remove_vertex(&mut self, key: &K) -> Result<KeyedDiff<K, V, E>, KeyedError<K>> {
+        let index = self.vertex_index(key)?;
+        let vertex = self.graph.get_vertex(index).unwrap();
+
+        let edges = vertex
+            .get_connected_edges()
+            .into_iter()
+            .map(|edge| self.keyed_edge(edge))
+            .collect();
+        let diff = KeyedDiff::RemoveVertex {
+            key: key.clone(),
+            data: vertex.data.clone(),
+            edges,
+        };
+
+        self.apply_diff(&diff)?;
+
+        Ok(diff)
+    }
+
+    pub fn remove_edge(&mut self, key: &K) -> Result<KeyedDiff<K, V, E>, KeyedError<K>> {
+        let index = self.edge_index(key)?;
+        let diff = KeyedDiff::RemoveEdge(self.keyed_edge(index));
+
+        self.apply_diff(&diff)?;
+
+        Ok(diff)
+    }
+
+    pub fn update_vertex(
+        &mut self,
+        key: &K,
+        value: V,
+    ) -> Result<KeyedDiff<K, V, E>, KeyedError<K>> {
+        let index = self.vertex_index(key)?;
+        let diff = KeyedDiff::UpdateVertexData {
+            key: key.clone(),
+            before: self.graph[index].data.clone(),
+            after: value,
+        };
+
+        self.apply_diff(&diff)?;
+
+        Ok(diff)
+    }
+
+    pub fn update_edge(&mut self, key: &K, value: E) -> Result<KeyedDiff<K, V, E>, KeyedError<K>> {
+        let index = self.edge_index(key)?;
+        let diff = KeyedDiff::UpdateEdgeData {
+            key: key.clone(),
+            before: self.graph[index].data.clone(),
+            after: value,
+        };
+
+        self.apply_diff(&diff)?;
+
+        Ok(diff)
+    }
+
+    /// Apply a keyed diff, which may come from a graph with a different slot
+    /// layout. Returns the diff that was applied to the underlying graph.
+    /// Either all of it is applied, or the graph is left as it was.
+    pub fn apply_diff(
+        &mut self,
+        diff: &KeyedDiff<K, V, E>,
+    ) -> Result<GraphDiff<V, E>, KeyedError<K>> {
+        self.run_atomic(diff, false)
+    }
+
+    /// Roll back a keyed diff. Vertices and edges that are put back get new
+    /// indices, but keep their keys. Returns the diff that was applied to the
+    /// underlying graph. Either all of it is rolled back, or the graph is
+    /// left as it was.
+    pub fn rollback_diff(
+        &mut self,
+        diff: &KeyedDiff<K, V, E>,
+    ) -> Result<GraphDiff<V, E>, KeyedError<K>> {
+        self.run_atomic(diff, true)
+    }
+
+    /// Apply a diff that only changes data, such as the one returned by
+    /// [`KeyedGraph::apply_diff`] for an update. Fails with `NotKeyed`
+    /// if it adds or removes a vertex or edge, as that would leave the keys
+    /// out of sync; use a [`KeyedDiff`] instead. Either all of it is applied,
+    /// or the graph is left as it was.
+    pub fn apply_graph_diff<VP: Patch<V>, EP: Patch<E>>(
+        &mut self,
+        diff: &GraphDiff<V, E, VP, EP>,
+    ) -> Result<(), KeyedError<K>> {
+        assert_data_only(diff)?;
+
+        Ok(self.graph.apply_diff_ref(diff)?)
+    }
+
+    /// Roll back a diff that only changes data. See
+    /// [`KeyedGraph::apply_graph_diff`].
+    pub fn rollback_graph_diff<VP: Patch<V>, EP: Patch<E>>(
+        &mut self,
+        diff: &GraphDiff<V, E, VP, EP>,
+    ) -> Result<(), KeyedError<K>> {
+        assert_data_only(diff)?;
+
+        Ok(self.graph.rollback_diff_ref(diff)?)
+    }
+
+    fn run_atomic(
+        &mut self,
+        diff: &KeyedDiff<K, V, E>,
+        inverted: bool,
+    ) -> Result<GraphDiff<V, E>, KeyedError<K>> {
+        let mut applied = Vec::new();
+        let mut links = Vec::new();
+
+        match self.apply(diff, inverted, &mut applied, &mut links) {
+            Ok(()) => Ok(GraphDiff::Group(applied)),
+            Err(err) => {
+                self.graph
+                    .rollback_diffs(applied)
+                    .expect("Keyed graph has become corrupted while rolling back a failed diff");
+
+                for link in links.into_iter().rev() {
+                    match link {
+                        Link::Vertex(key) => self.unlink_vertex(&key),
+                        Link::Edge(key) => self.unlink_edge(&key),
+                        Link::UnlinkVertex(key, index) => self.link_vertex(key, index),
+                        Link::UnlinkEdge(key, index) => self.link_edge(key, index),
+                    }
+                }
+
+                Err(err)
+            }
+        }
+    }
+
+    fn apply(
+        &mut self,
+        diff: &KeyedDiff<K, V, E>,
+        inverted: bool,
+        applied: &mut Vec<GraphDiff<V, E>>,
+        links: &mut Vec<Link<K>>,
+    ) -> Result<(), KeyedError<K>> {
+        match (diff, inverted) {
+            (KeyedDiff::AddVertex { key, data }, false) => {
+                self.add_keyed_vertex(key, data, applied, links)?;
+            }
+            (KeyedDiff::AddVertex { key, .. }, true)
+            | (KeyedDiff::RemoveVertex { key, .. }, false) => {
+                let index = self.vertex_index(key)?;
+                let diff = self.graph.remove_vertex(index)?;
+
+                if let GraphDiff::RemoveVertex(removed) = &diff {
+                    for removed_edge in &removed.removed_edges {
+                        let key = self.edge_keys[removed_edge.edge_index.0.index].clone();
+                        let key = key.expect("Every edge in a keyed graph has a key");
+                        self.unlink_edge(&key);
+                        links.push(Link::UnlinkEdge(key, removed_edge.edge_index));
+                    }
+                }
+
+                self.unlink_vertex(key);
+                links.push(Link::UnlinkVertex(key.clone(), index));
+                applied.push(diff);
+            }
+            (KeyedDiff::RemoveVertex { key, data, edges }, true) => {
+                self.add_keyed_vertex(key, data, applied, links)?;
+
+                for edge in edges {
+                    self.add_keyed_edge(edge, applied, links)?;
+                }
+            }
+            (KeyedDiff::AddEdge(edge), false) | (KeyedDiff::RemoveEdge(edge), true) => {
+                self.add_keyed_edge(edge, applied, links)?;
+            }
+            (KeyedDiff::AddEdge(edge), true) | (KeyedDiff::RemoveEdge(edge), false) => {
+                let index = self.edge_index(&edge.key)?;
+                applied.push(self.graph.remove_edge(index)?);
+
+                self.unlink_edge(&edge.key);
+                links.push(Link::UnlinkEdge(edge.key.clone(), index));
+            }
+            (
+                KeyedDiff::UpdateVertexData {
+                    key, before, after, ..
+                },
+                inverted,
+            ) => {
+                let index = self.vertex_index(key)?;
+                let value = if inverted { before } else { after };
+
+                let (_, diff) = self.graph.update_vertex(index, value.clone())?;
+                applied.push(diff);
+            }
+            (
+                KeyedDiff::UpdateEdgeData {
+                    key, before, after, ..
+                },
+                inverted,
+            ) => {
+                let index = self.edge_index(key)?;
+                let value = if inverted { before } else { after };
+
+                let (_, diff) = self.graph.update_edge(index, value.clone())?;
+                applied.push(diff);
+            }
+            (KeyedDiff::Group(diffs), false) => {
+                for diff in diffs {
+                    self.apply(diff, false, applied, links)?;
+                }
+            }
+            (KeyedDiff::Group(diffs), true) => {
+                for diff in diffs.iter().rev() {
+                    self.apply(diff, true, applied, links)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_keyed_vertex(
+        &mut self,
+        key: &K,
+        data: &V,
+        applied: &mut Vec<GraphDiff<V, E>>,
+        links: &mut Vec<Link<K>>,
+    ) -> Result<(), KeyedError<K>> {
+        if self.vertex_indexes.contains_key(key) {
+            return Err(KeyedError::KeyInUse { key: key.clone() });
+        }
+
+        let (index, diff) = self.graph.add_vertex(data.clone());
+        self.link_vertex(key.clone(), index);
+        links.push(Link::Vertex(key.clone()));
+        applied.push(diff);
+
+        Ok(())
+    }
+
+    fn add_keyed_edge(
+        &mut self,
+        edge: &KeyedEdge<K, E>,
+        applied: &mut Vec<GraphDiff<V, E>>,
+        links: &mut Vec<Link<K>>,
+    ) -> Result<(), KeyedError<K>> {
+        if self.edge_indexes.contains_key(&edge.key) {
+            return Err(KeyedError::KeyInUse {
+                key: edge.key.clone(),
+            });
+        }
+
+        let from = self.vertex_index(&edge.from)?;
+        let to = self.vertex_index(&edge.to)?;
+
+        let (index, diff) = self.graph.add_edge(from, to, edge.data.clone())?;
+        self.link_edge(edge.key.clone(), index);
+        links.push(Link::Edge(edge.key.clone()));
+        applied.push(diff);
+
+        Ok(())
+    }
+
+    fn keyed_edge(&self, index: EdgeIndex) -> KeyedEdge<K, E> {
+        let edge = &self.graph.get_edges()[index.0];
+        let vertex_key = |index: VertexIndex| self.get_vertex_key(index).unwrap().clone();
+
+        KeyedEdge {
+            key: self.get_edge_key(index).unwrap().clone(),
+            from: vertex_key(edge.get_from()),
+            to: vertex_key(edge.get_to()),
+            data: edge.data.clone(),
+        }
+    }
+
+    fn vertex_index(&self, key: &K) -> Result<VertexIndex, KeyedError<K>> {
+        self.get_vertex_index(key)
+            .ok_or_else(|| KeyedError::KeyDoesNotExist { key: key.clone() })
+    }
+
+    fn edge_index(&self, key: &K) -> Result<EdgeIndex, KeyedError<K>> {
+        self.get_edge_index(key)
+            .ok_or_else(|| KeyedError::KeyDoesNotExist { key: key.clone() })
+    }
+
+    fn link_vertex(&mut self, key: K, index: VertexIndex) {
+        set_slot(&mut self.vertex_keys, index.0.index, Some(key.clone()));
+        self.vertex_indexes.insert(key, index);
+    }
+
+    fn link_edge(&mut self, key: K, index: EdgeIndex) {
+        set_slot(&mut self.edge_keys, index.0.index, Some(key.clone()));
+        self.edge_indexes.insert(key, index);
+    }
+
+    fn unlink_vertex(&mut self, key: &K) {
+        if let Some(index) = self.vertex_indexes.remove(key) {
+            set_slot(&mut self.vertex_keys, index.0.index, None);
+        }
+    }
+
+    fn unlink_edge(&mut self, key: &K) {
+        if let Some(index) = self.edge_indexes.remove(key) {
+            set_slot(&mut self.edge_keys, index.0.index, None);
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone + Debug, V: Clone, E: Clone> Default for KeyedGraph<K, V, E> {
+    fn default() -> Self {
+        KeyedGraph::new()
+    }
+}
+
+fn assert_data_only<K: Debug, V, E, VP, EP>(
+    diff: &GraphDiff<V, E, VP, EP>,
+) -> Result<(), KeyedError<K>> {
+    let mut unkeyed = None;
+    diff.for_each_access(&mut |index, access| {
+        if access == SlotAccess::Existence && unkeyed.is_none() {
+            unkeyed = Some(index);
+        }
+    });
+
+    match unkeyed {
+        Some(index) => Err(KeyedError::NotKeyed { index }),
+        None => Ok(()),
+    }
+}
+
+fn set_slot<K>(keys: &mut Vec<Option<K>>, slot: usize, key: Option<K>) {
+    if keys.len() <= slot {
+        keys.resize_with(slot + 1, || None);
+    }
+
+    keys[slot] = key;
+}
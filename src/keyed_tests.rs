@@ -0,0 +1,112 @@
+use crate::{KeyedDiff, KeyedError, KeyedGraph};
+
+#[test]
+fn test_keyed_graph_lookup() {
+    let mut graph: KeyedGraph<u128, i32, i32> = KeyedGraph::new();
+
+    let (first, _) = graph.add_vertex(100, 1).unwrap();
+    let (second, _) = graph.add_vertex(200, 2).unwrap();
+    let (edge, _) = graph.add_edge(300, 100, 200, 10).unwrap();
+
+    assert_eq!(graph.get_vertex_index(&100), Some(first));
+    assert_eq!(graph.get_vertex_key(second), Some(&200));
+    assert_eq!(graph.get_edge_key(edge), Some(&300));
+    assert!(matches!(
+        graph.add_vertex(100, 3),
+        Err(KeyedError::KeyInUse { key: 100 })
+    ));
+    assert!(matches!(
+        graph.update_vertex(&400, 4),
+        Err(KeyedError::KeyDoesNotExist { key: 400 })
+    ));
+
+    let remove = graph.remove_vertex(&200).unwrap();
+    assert!(matches!(&remove, KeyedDiff::RemoveVertex { edges, .. } if edges.len() == 1));
+    assert_eq!(graph.get_vertex_index(&200), None);
+    assert_eq!(graph.get_edge_index(&300), None);
+    assert_eq!(graph.get_vertex_key(second), None);
+
+    // restored verticies and edges keep their keys
+    graph.rollback_diff(&remove).unwrap();
+    let second = graph.get_vertex_index(&200).unwrap();
+    let edge = graph.get_edge_index(&300).unwrap();
+    assert_eq!(graph.graph()[second].data(), &2);
+    assert_eq!(graph.graph()[edge].get_to(), second);
+    assert_eq!(graph.get_vertex_key(second), Some(&200));
+}
+
+#[test]
+fn test_keyed_diffs_replay_across_layouts() {
+    let mut source: KeyedGraph<u128, i32, i32> = KeyedGraph::new();
+    let mut target: KeyedGraph<u128, i32, i32> = KeyedGraph::new();
+
+    // give the target a different slot layout
+    target.add_vertex(1, 0).unwrap();
+    target.add_vertex(2, 0).unwrap();
+    target.remove_vertex(&1).unwrap();
+
+    let diffs = [
+        source.add_vertex(100, 1).unwrap().1,
+        source.add_vertex(200, 2).unwrap().1,
+        source.add_edge(300, 100, 200, 10).unwrap().1,
+        source.update_vertex(&100, 5).unwrap(),
+    ];
+    for diff in &diffs {
+        target.apply_diff(diff).unwrap();
+    }
+
+    let first = target.get_vertex_index(&100).unwrap();
+    assert_ne!(Some(first), source.get_vertex_index(&100));
+    assert_eq!(target.graph()[first].data(), &5);
+    let edge = target.get_edge_index(&300).unwrap();
+    assert_eq!(target.graph()[edge].get_from(), first);
+
+    // a diff that fails halfway leaves everything as it was
+    let group = KeyedDiff::Group(vec![
+        KeyedDiff::AddVertex { key: 400, data: 4 },
+        KeyedDiff::AddVertex { key: 100, data: 1 },
+    ]);
+    assert!(target.apply_diff(&group).is_err());
+    assert_eq!(target.get_vertex_index(&400), None);
+    assert_eq!(target.graph().vertex_indexes().count(), 3);
+
+    for diff in diffs.iter().rev() {
+        target.rollback_diff(diff).unwrap();
+    }
+    assert_eq!(target.graph().vertex_indexes().count(), 1);
+    assert_eq!(target.get_vertex_index(&100), None);
+}
+
+#[test]
+fn test_keyed_graph_plain_diffs() {
+    let mut graph: KeyedGraph<u128, i32, i32> = KeyedGraph::new();
+
+    let (_, add) = graph.add_vertex(100, 1).unwrap();
+    let update = graph.update_vertex(&100, 2).unwrap();
+    let update = graph.rollback_diff(&update).unwrap();
+    assert_eq!(
+        graph.graph()[graph.get_vertex_index(&100).unwrap()].data(),
+        &1
+    );
+
+    // diffs that only change data can be applied as they are
+    graph.rollback_graph_diff(&update).unwrap();
+    assert_eq!(
+        graph.graph()[graph.get_vertex_index(&100).unwrap()].data(),
+        &2
+    );
+    graph.apply_graph_diff(&update).unwrap();
+    assert_eq!(
+        graph.graph()[graph.get_vertex_index(&100).unwrap()].data(),
+        &1
+    );
+
+    // but adding or removing anything would leave the keys out of sync
+    let remove = graph.rollback_diff(&add).unwrap();
+    assert!(matches!(
+        graph.rollback_graph_diff(&remove),
+        Err(KeyedError::NotKeyed { .. })
+    ));
+    assert_eq!(graph.graph().vertex_indexes().count(), 0);
+    assert_eq!(graph.get_vertex_index(&100), None);
+}
@@ -3,16 +3,58 @@
 
 extern crate alloc;
 
+mod budget;
+mod coalesce;
+mod compare;
 mod errors;
+#[cfg(feature = "serde")]
+pub mod full_fidelity;
 mod gen_vec;
 mod graph;
 mod graph_diff;
+mod history;
+mod keyed;
+mod merge;
+mod patch;
+mod rebase;
+mod recorder;
+mod replicated;
+mod transaction;
+mod undo_tree;
+mod validate;
 
+pub use budget::{HistoryBudget, HistoryStats, RetentionPolicy, SizeEstimator};
+pub use coalesce::coalesce;
 pub use errors::*;
 pub use gen_vec::GenVec;
 pub use gen_vec::Index;
 pub use graph::*;
 pub use graph_diff::GraphDiff;
+pub use history::{Checkpoint, History};
+pub use keyed::{KeyedDiff, KeyedEdge, KeyedGraph};
+pub use merge::{merge3, Merge};
+pub use patch::{NoPatch, Patch, Patchable};
+pub use rebase::{ConflictResolver, DefaultResolver, Rebase, RebaseConflict, Resolution};
+pub use recorder::Recorder;
+pub use replicated::{
+    ConflictPolicy, EdgeId, Operation, OperationId, OperationKind, ReplicaId, ReplicatedGraph,
+    VertexId,
+};
+pub use transaction::Transaction;
+pub use undo_tree::{StateIndex, UndoState, UndoTree};
+pub use validate::{ConnectionList, Inconsistency, SlotKind};
 
 #[cfg(test)]
 mod graph_tests;
+#[cfg(test)]
+mod history_tests;
+#[cfg(test)]
+mod keyed_tests;
+#[cfg(test)]
+mod merge_tests;
+#[cfg(test)]
+mod rebase_tests;
+#[cfg(test)]
+mod replicated_tests;
+#[cfg(test)]
+mod undo_tree_tests;
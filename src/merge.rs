@@ -0,0 +1,117 @@
+use alloc::vec::Vec;
+
+use crate::{
+    errors::GraphError,
+    graph::{Graph, GraphIndex},
+    graph_diff::GraphDiff,
+    rebase::{ConflictResolver, RebaseConflict, Resolution},
+};
+
+/// The result of [`merge3`].
+#[derive(Debug, Clone)]
+pub struct Merge<V, E> {
+    graph: Graph<V, E>,
+    diffs: Vec<GraphDiff<V, E>>,
+    conflicts: Vec<RebaseConflict>,
+}
+
+impl<V, E> Merge<V, E> {
+    pub fn get_graph(&self) -> &Graph<V, E> {
+        &self.graph
+    }
+
+    pub fn into_graph(self) -> Graph<V, E> {
+        self.graph
+    }
+
+    /// The diffs that turn `ours` into the merged graph, applied in order.
+    pub fn get_diffs(&self) -> &[GraphDiff<V, E>] {
+        &self.diffs
+    }
+
+    pub fn get_conflicts(&self) -> &[RebaseConflict] {
+        &self.conflicts
+    }
+}
+
+/// Merge two graphs that both started out as `base`, say for a git merge
+/// driver. Changes only one side made are kept, and anything both sides
+/// changed is decided by `resolver` and reported in the returned
+/// [`Merge`]'s conflicts.
+///
+/// This rebases `theirs` onto `ours` itself (see [`Graph::rebase_with`]), so
+/// "local" is `theirs` and "remote" is `ours`. Indices in the conflicts are `base`'s, or
+/// for anything `theirs` added, the ones from `base.diff_against(theirs)`.
+///
+/// Both sides are compared slot by slot (see [`Graph::diff_against`]), so a
+/// vertex or edge counts as the same on both sides only if it kept its index.
+/// Both sides updating something to the same value isn't a conflict.
+pub fn merge3<V, E, R>(
+    base: &Graph<V, E>,
+    ours: &Graph<V, E>,
+    theirs: &Graph<V, E>,
+    resolver: &mut R,
+) -> Result<Merge<V, E>, GraphError>
+where
+    V: Clone + PartialEq,
+    E: Clone + PartialEq,
+    R: ConflictResolver<V, E>,
+{
+    let local = base.diff_against(theirs);
+    let remote = base.diff_against(ours);
+
+    // `remote` only says what changed, `ours` also has the generations its
+    // open slots were left at, which the diffs need to match
+    let (graph, rebase) = base.rebase_onto(
+        ours.clone(),
+        &local,
+        &remote,
+        &mut SkipAgreeing { resolver },
+    )?;
+
+    let mut conflicts = rebase.get_conflicts().to_vec();
+    conflicts.retain(|conflict| match conflict {
+        RebaseConflict::BothUpdated {
+            index: GraphIndex::Vertex(index),
+        } => ours.get_vertex_data(*index) != theirs.get_vertex_data(*index),
+        RebaseConflict::BothUpdated {
+            index: GraphIndex::Edge(index),
+        } => ours.get_edge_data(*index) != theirs.get_edge_data(*index),
+        _ => true,
+    });
+
+    Ok(Merge {
+        graph,
+        diffs: rebase.into_diffs(),
+        conflicts,
+    })
+}
+
+/// Only asks `resolver` about updates that disagree.
+struct SkipAgreeing<'a, R> {
+    resolver: &'a mut R,
+}
+
+impl<V: PartialEq, E: PartialEq, R: ConflictResolver<V, E>> ConflictResolver<V, E>
+    for SkipAgreeing<'_, R>
+{
+    fn resolve_vertex_data(&mut self, base: &V, remote: &V, local: &V) -> Resolution<V> {
+        if remote == local {
+            Resolution::Local
+        } else {
+            self.resolver.resolve_vertex_data(base, remote, local)
+        }
+    }
+
+    fn resolve_edge_data(&mut self, base: &E, remote: &E, local: &E) -> Resolution<E> {
+        if remote == local {
+            Resolution::Local
+        } else {
+            self.resolver.resolve_edge_data(base, remote, local)
+        }
+    }
+
+    fn keep_removed(&mut self, conflict: &RebaseConflict) -> bool {
+        self.resolver.keep_removed(conflict)
+    }
+}
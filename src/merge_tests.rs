@@ -0,0 +1,153 @@
+use alloc::vec::Vec;
+
+use crate::{
+    merge3, ConflictResolver, DefaultResolver, Graph, GraphIndex, RebaseConflict, Resolution,
+};
+
+fn vertex_data(graph: &Graph<i32, i32>) -> Vec<i32> {
+    let mut data: Vec<i32> = graph.vertex_data_iter().map(|(_, data)| *data).collect();
+    data.sort();
+
+    data
+}
+
+/// Adds up concurrent updates, and keeps anything still in use.
+struct Sum;
+
+impl ConflictResolver<i32, i32> for Sum {
+    fn resolve_vertex_data(&mut self, base: &i32, remote: &i32, local: &i32) -> Resolution<i32> {
+        Resolution::Value(remote + local - base)
+    }
+
+    fn keep_removed(&mut self, _conflict: &RebaseConflict) -> bool {
+        true
+    }
+}
+
+#[test]
+fn test_merge3() {
+    let mut base: Graph<i32, i32> = Graph::new();
+    let a = base.add_vertex_untracked(1);
+    let b = base.add_vertex_untracked(2);
+    let c = base.add_vertex_untracked(3);
+    let ab = base.add_edge_untracked(a, b, 10).unwrap();
+
+    let mut ours = base.clone();
+    ours.update_vertex(a, 5).unwrap();
+    ours.update_vertex(b, 20).unwrap();
+    ours.add_vertex(4);
+
+    let mut theirs = base.clone();
+    theirs.update_vertex(b, 20).unwrap();
+    theirs.update_vertex(c, 30).unwrap();
+    theirs.update_edge(ab, 11).unwrap();
+    theirs.add_vertex(6);
+
+    let merge = merge3(&base, &ours, &theirs, &mut DefaultResolver).unwrap();
+
+    // both updating b to the same value isn't a conflict
+    assert!(merge.get_conflicts().is_empty());
+    assert_eq!(vertex_data(merge.get_graph()), [4, 5, 6, 20, 30]);
+    assert_eq!(merge.get_graph().get_edge_data(ab), Some(&11));
+
+    ours.apply_diffs(merge.get_diffs().to_vec()).unwrap();
+    assert_eq!(vertex_data(&ours), vertex_data(merge.get_graph()));
+    assert!(ours.diff_against(merge.get_graph()).is_empty());
+}
+
+#[test]
+fn test_merge3_diffs_apply_to_ours() {
+    let base: Graph<i32, i32> = Graph::new();
+
+    // leaves the slot open at a later generation than in `base`
+    let mut ours = base.clone();
+    let (added, _) = ours.add_vertex(1);
+    ours.remove_vertex(added).unwrap();
+
+    let mut theirs = base.clone();
+    theirs.add_vertex(2);
+
+    let merge = merge3(&base, &ours, &theirs, &mut DefaultResolver).unwrap();
+    ours.apply_diffs(merge.get_diffs().to_vec()).unwrap();
+
+    assert_eq!(vertex_data(&ours), [2]);
+    assert!(ours.diff_against(merge.get_graph()).is_empty());
+    assert!(merge.get_graph().diff_against(&ours).is_empty());
+}
+
+#[test]
+fn test_merge3_conflicts() {
+    let mut base: Graph<i32, i32> = Graph::new();
+    let a = base.add_vertex_untracked(1);
+    let b = base.add_vertex_untracked(2);
+    let c = base.add_vertex_untracked(3);
+
+    let mut ours = base.clone();
+    ours.update_vertex(a, 5).unwrap();
+    ours.remove_vertex(b).unwrap();
+    ours.add_edge(a, c, 10).unwrap();
+
+    let mut theirs = base.clone();
+    theirs.update_vertex(a, 7).unwrap();
+    theirs.add_edge(b, a, 20).unwrap();
+    theirs.remove_vertex(c).unwrap();
+
+    let merge = merge3(&base, &ours, &theirs, &mut DefaultResolver).unwrap();
+    assert!(matches!(
+        merge.get_conflicts(),
+        [
+            RebaseConflict::RemovedConnected { vertex, .. },
+            RebaseConflict::BothUpdated {
+                index: GraphIndex::Vertex(updated)
+            },
+            RebaseConflict::EndpointRemoved { vertex: endpoint, .. },
+        ] if *vertex == c && *updated == a && *endpoint == b
+    ));
+    assert_eq!(vertex_data(merge.get_graph()), [7]);
+    assert_eq!(merge.get_graph().edge_indexes().count(), 0);
+
+    let merge = merge3(&base, &ours, &theirs, &mut Sum).unwrap();
+    assert_eq!(merge.get_conflicts().len(), 3);
+    assert_eq!(vertex_data(merge.get_graph()), [2, 3, 11]);
+
+    let mut edge_data: Vec<i32> = merge
+        .get_graph()
+        .edge_data_iter()
+        .map(|(_, data)| *data)
+        .collect();
+    edge_data.sort();
+    assert_eq!(edge_data, [10, 20]);
+}
+
+#[test]
+fn test_rebase_with_keep_removed() {
+    let mut base: Graph<i32, i32> = Graph::new();
+    let a = base.add_vertex_untracked(1);
+    let b = base.add_vertex_untracked(2);
+
+    let mut local_graph = base.clone();
+    let (ab, add_edge) = local_graph.add_edge(a, b, 10).unwrap();
+
+    let mut remote_graph = base.clone();
+    let remove = remote_graph.remove_vertex(b).unwrap();
+
+    let rebase = base.rebase_with(&[add_edge], &[remove], &mut Sum).unwrap();
+    assert_eq!(
+        rebase.get_conflicts(),
+        [RebaseConflict::EndpointRemoved {
+            edge: ab,
+            vertex: b
+        }]
+    );
+
+    // b comes back with its base data, at a new index
+    remote_graph
+        .apply_diffs(rebase.get_diffs().to_vec())
+        .unwrap();
+    let restored = rebase.map_vertex(b);
+    assert_ne!(restored, b);
+    assert_eq!(remote_graph.get_vertex_data(restored), Some(&2));
+
+    let edge = &remote_graph[rebase.map_edge(ab)];
+    assert_eq!((edge.from, edge.to, edge.data), (a, restored, 10));
+}
@@ -0,0 +1,162 @@
+use core::fmt::Debug;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use snafu::OptionExt;
+
+use crate::{
+    errors::GraphError,
+    graph::{EdgeIndex, Graph, VertexIndex},
+    graph_diff::{GraphDiff, PatchEdgeData, PatchVertexData},
+    EdgeDoesNotExistSnafu, VertexDoesNotExistSnafu,
+};
+
+/// A change to some data of type `T`, stored in place of full before and after
+/// copies of it. See [`Patchable`].
+pub trait Patch<T>: Clone + Debug {
+    /// Turn the old value into the new one.
+    fn apply(&self, data: &mut T);
+
+    /// Turn the new value back into the old one.
+    fn revert(&self, data: &mut T);
+}
+
+/// Data that can describe a change to itself as a patch, so updates only need
+/// to store what changed instead of full before/after copies.
+///
+/// Only needed for [`crate::Graph::patch_vertex`] and friends; data that
+/// doesn't implement this can still be updated with `update_vertex`.
+pub trait Patchable: Sized {
+    type Patch: Patch<Self>;
+
+    /// Compute a patch that turns `self` into `after`.
+    fn make_patch(&self, after: &Self) -> Self::Patch;
+}
+
+/// The patch type of data that isn't patched. It has no values, so a diff
+/// using it never holds a patch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum NoPatch {}
+
+impl<T> Patch<T> for NoPatch {
+    fn apply(&self, _data: &mut T) {
+        match *self {}
+    }
+
+    fn revert(&self, _data: &mut T) {
+        match *self {}
+    }
+}
+
+impl<V: Patchable, E> Graph<V, E> {
+    /// Apply a patch to a vertex's data, recording it as a `PatchVertexData`
+    /// diff. `EP` is the patch type of edges in the returned diff.
+    pub fn patch_vertex<EP>(
+        &mut self,
+        index: VertexIndex,
+        patch: V::Patch,
+    ) -> Result<GraphDiff<V, E, V::Patch, EP>, GraphError> {
+        let vertex = self
+            .verticies
+            .get_mut(index.0)
+            .context(VertexDoesNotExistSnafu { index })?;
+
+        patch.apply(&mut vertex.data);
+
+        Ok(GraphDiff::PatchVertexData(PatchVertexData {
+            index,
+            patch,
+            reverted: false,
+        }))
+    }
+}
+
+impl<V: Patchable + Clone, E> Graph<V, E> {
+    /// Mutate a vertex's data in place, recording the change as a
+    /// `PatchVertexData` diff (see [`Patchable::make_patch`]). Returns whatever
+    /// `f` returns.
+    #[allow(clippy::type_complexity)]
+    pub fn patch_vertex_with<T, F, EP>(
+        &mut self,
+        index: VertexIndex,
+        f: F,
+    ) -> Result<(T, GraphDiff<V, E, V::Patch, EP>), GraphError>
+    where
+        F: FnOnce(&mut V) -> T,
+    {
+        let vertex = self
+            .verticies
+            .get_mut(index.0)
+            .context(VertexDoesNotExistSnafu { index })?;
+
+        let before = vertex.data.clone();
+        let result = f(&mut vertex.data);
+        let patch = before.make_patch(&vertex.data);
+
+        Ok((
+            result,
+            GraphDiff::PatchVertexData(PatchVertexData {
+                index,
+                patch,
+                reverted: false,
+            }),
+        ))
+    }
+}
+
+impl<V, E: Patchable> Graph<V, E> {
+    /// Apply a patch to an edge's data, recording it as a `PatchEdgeData`
+    /// diff. `VP` is the patch type of vertices in the returned diff.
+    pub fn patch_edge<VP>(
+        &mut self,
+        index: EdgeIndex,
+        patch: E::Patch,
+    ) -> Result<GraphDiff<V, E, VP, E::Patch>, GraphError> {
+        let edge = self
+            .edges
+            .get_mut(index.0)
+            .context(EdgeDoesNotExistSnafu { index })?;
+
+        patch.apply(&mut edge.data);
+
+        Ok(GraphDiff::PatchEdgeData(PatchEdgeData {
+            index,
+            patch,
+            reverted: false,
+        }))
+    }
+}
+
+impl<V, E: Patchable + Clone> Graph<V, E> {
+    /// Mutate an edge's data in place, recording the change as a
+    /// `PatchEdgeData` diff (see [`Patchable::make_patch`]). Returns whatever
+    /// `f` returns.
+    #[allow(clippy::type_complexity)]
+    pub fn patch_edge_with<T, F, VP>(
+        &mut self,
+        index: EdgeIndex,
+        f: F,
+    ) -> Result<(T, GraphDiff<V, E, VP, E::Patch>), GraphError>
+    where
+        F: FnOnce(&mut E) -> T,
+    {
+        let edge = self
+            .edges
+            .get_mut(index.0)
+            .context(EdgeDoesNotExistSnafu { index })?;
+
+        let before = edge.data.clone();
+        let result = f(&mut edge.data);
+        let patch = before.make_patch(&edge.data);
+
+        Ok((
+            result,
+            GraphDiff::PatchEdgeData(PatchEdgeData {
+                index,
+                patch,
+                reverted: false,
+            }),
+        ))
+    }
+}
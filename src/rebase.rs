@@ -0,0 +1,566 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec::Vec,
+};
+
+use crate::{
+    errors::GraphError,
+    graph::{EdgeIndex, Graph, GraphIndex, VertexIndex},
+    graph_diff::{GraphDiff, PatchEdgeData, PatchVertexData, SlotAccess},
+    patch::{NoPatch, Patch},
+};
+
+/// Something both sides of a [`Graph::rebase`] changed. Indices are the ones
+/// used by the local diffs. What happens to each is described for the
+/// [`DefaultResolver`], see [`ConflictResolver`] for the alternatives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RebaseConflict {
+    /// Both sides updated the data. The local update is applied on top of the
+    /// remote one.
+    BothUpdated { index: GraphIndex },
+    /// The local side updated something the remote side removed. The update
+    /// is dropped.
+    UpdatedRemoved { index: GraphIndex },
+    /// The local side removed something the remote side updated. It's removed
+    /// anyway.
+    RemovedUpdated { index: GraphIndex },
+    /// The local side added an edge to a vertex the remote side removed. The
+    /// edge is dropped, along with any later local changes to it.
+    EndpointRemoved {
+        edge: EdgeIndex,
+        vertex: VertexIndex,
+    },
+    /// The local side removed a vertex the remote side added an edge to (the
+    /// edge's index is the remote one). It's removed anyway, along with the
+    /// edge.
+    RemovedConnected {
+        vertex: VertexIndex,
+        edge: EdgeIndex,
+    },
+}
+
+/// Which value to keep when both sides updated the same data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution<T> {
+    Local,
+    Remote,
+    /// A combination of the two, say.
+    Value(T),
+}
+
+/// Decides conflicts during [`Graph::rebase_with`] and [`crate::merge3`]. By
+/// default local updates win, and removals win over everything else.
+pub trait ConflictResolver<V, E> {
+    /// Both sides updated a vertex's data. Not called for patches, which are
+    /// always applied on top.
+    fn resolve_vertex_data(&mut self, base: &V, remote: &V, local: &V) -> Resolution<V> {
+        let _ = (base, remote, local);
+
+        Resolution::Local
+    }
+
+    /// Both sides updated an edge's data. Not called for patches, which are
+    /// always applied on top.
+    fn resolve_edge_data(&mut self, base: &E, remote: &E, local: &E) -> Resolution<E> {
+        let _ = (base, remote, local);
+
+        Resolution::Local
+    }
+
+    /// One side removed something the other still uses. Returning `true`
+    /// keeps it instead:
+    ///
+    /// - `UpdatedRemoved` brings it back with the local update (edges only
+    ///   if both endpoints still exist).
+    /// - `RemovedUpdated` and `RemovedConnected` skip the local removal.
+    /// - `EndpointRemoved` brings the vertex back with its base data, so the
+    ///   edge can be added.
+    ///
+    /// Anything brought back gets a new index, see [`Rebase::map_vertex`].
+    fn keep_removed(&mut self, conflict: &RebaseConflict) -> bool {
+        let _ = conflict;
+
+        false
+    }
+}
+
+/// The [`ConflictResolver`] defaults: local updates win, and removals win
+/// over everything else.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultResolver;
+
+impl<V, E> ConflictResolver<V, E> for DefaultResolver {}
+
+/// Local diffs rewritten to apply after remote ones, see [`Graph::rebase`].
+#[derive(Debug, Clone)]
+pub struct Rebase<V, E, VP = NoPatch, EP = NoPatch> {
+    diffs: Vec<GraphDiff<V, E, VP, EP>>,
+    conflicts: Vec<RebaseConflict>,
+    vertex_map: BTreeMap<VertexIndex, VertexIndex>,
+    edge_map: BTreeMap<EdgeIndex, EdgeIndex>,
+}
+
+impl<V, E, VP, EP> Rebase<V, E, VP, EP> {
+    /// One diff for each local diff, in the same order. Local diffs that were
+    /// dropped entirely become empty groups.
+    pub fn get_diffs(&self) -> &[GraphDiff<V, E, VP, EP>] {
+        &self.diffs
+    }
+
+    pub fn into_diffs(self) -> Vec<GraphDiff<V, E, VP, EP>> {
+        self.diffs
+    }
+
+    pub fn get_conflicts(&self) -> &[RebaseConflict] {
+        &self.conflicts
+    }
+
+    /// Where a vertex the local diffs refer to ended up. Vertices the local
+    /// side didn't add (or a resolver didn't bring back) keep their index.
+    pub fn map_vertex(&self, index: VertexIndex) -> VertexIndex {
+        self.vertex_map.get(&index).copied().unwrap_or(index)
+    }
+
+    /// Where an edge the local diffs refer to ended up. Edges the local side
+    /// didn't add (or a resolver didn't bring back) keep their index.
+    pub fn map_edge(&self, index: EdgeIndex) -> EdgeIndex {
+        self.edge_map.get(&index).copied().unwrap_or(index)
+    }
+}
+
+impl<V: Clone, E: Clone> Graph<V, E> {
+    /// Rewrite `local` so it can be applied after `remote`, where both were
+    /// made against this graph. Vertices and edges added on both sides would
+    /// otherwise end up in the same slots, so the local ones are given new
+    /// indices.
+    ///
+    /// Local changes to things the remote side removed are dropped, and
+    /// anything both sides changed is reported as a [`RebaseConflict`]. See
+    /// [`Graph::rebase_with`] to decide conflicts differently.
+    pub fn rebase<VP: Patch<V>, EP: Patch<E>>(
+        &self,
+        local: &[GraphDiff<V, E, VP, EP>],
+        remote: &[GraphDiff<V, E, VP, EP>],
+    ) -> Result<Rebase<V, E, VP, EP>, GraphError> {
+        self.rebase_with(local, remote, &mut DefaultResolver)
+    }
+
+    /// Like [`Graph::rebase`], with conflicts decided by `resolver`. They're
+    /// still all reported.
+    pub fn rebase_with<VP: Patch<V>, EP: Patch<E>, R: ConflictResolver<V, E>>(
+        &self,
+        local: &[GraphDiff<V, E, VP, EP>],
+        remote: &[GraphDiff<V, E, VP, EP>],
+        resolver: &mut R,
+    ) -> Result<Rebase<V, E, VP, EP>, GraphError> {
+        let mut graph = self.clone();
+        graph.apply_diffs(remote.iter().cloned())?;
+
+        self.rebase_onto(graph, local, remote, resolver)
+            .map(|(_, rebase)| rebase)
+    }
+
+    /// Rebase onto `graph`, which is this graph once the remote side's changes
+    /// were made, and return it with the local ones applied too. It can differ
+    /// from applying `remote` to this graph in which generations open slots
+    /// are at, and the rebased diffs are only valid for `graph`.
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn rebase_onto<VP: Patch<V>, EP: Patch<E>, R: ConflictResolver<V, E>>(
+        &self,
+        graph: Graph<V, E>,
+        local: &[GraphDiff<V, E, VP, EP>],
+        remote: &[GraphDiff<V, E, VP, EP>],
+        resolver: &mut R,
+    ) -> Result<(Graph<V, E>, Rebase<V, E, VP, EP>), GraphError> {
+        let mut remote_updated = Vec::new();
+        for diff in remote {
+            diff.for_each_access(&mut |index, access| {
+                if access == SlotAccess::Data {
+                    remote_updated.push(index);
+                }
+            });
+        }
+
+        let mut rebaser = Rebaser {
+            base: self,
+            resolver,
+            graph,
+            remote_updated,
+            dropped_edges: BTreeSet::new(),
+            rebase: Rebase {
+                diffs: Vec::new(),
+                conflicts: Vec::new(),
+                vertex_map: BTreeMap::new(),
+                edge_map: BTreeMap::new(),
+            },
+        };
+
+        for diff in local {
+            let mut rebased = Vec::new();
+            rebaser.rebase_diff(diff, &mut rebased)?;
+
+            let rebased = if rebased.len() == 1 && !matches!(diff, GraphDiff::Group(_)) {
+                rebased.pop().unwrap()
+            } else {
+                GraphDiff::Group(rebased)
+            };
+            rebaser.rebase.diffs.push(rebased);
+        }
+
+        Ok((rebaser.graph, rebaser.rebase))
+    }
+}
+
+struct Rebaser<'a, V, E, VP, EP, R> {
+    base: &'a Graph<V, E>,
+    resolver: &'a mut R,
+    /// The base graph with the remote diffs and the local diffs rebased so far
+    /// applied
+    graph: Graph<V, E>,
+    remote_updated: Vec<GraphIndex>,
+    /// Edges the local side added that were dropped. Their indices may have
+    /// been taken by the remote side, so they can't be looked up.
+    dropped_edges: BTreeSet<EdgeIndex>,
+    rebase: Rebase<V, E, VP, EP>,
+}
+
+impl<V: Clone, E: Clone, VP: Patch<V>, EP: Patch<E>, R: ConflictResolver<V, E>>
+    Rebaser<'_, V, E, VP, EP, R>
+{
+    fn rebase_diff(
+        &mut self,
+        diff: &GraphDiff<V, E, VP, EP>,
+        out: &mut Vec<GraphDiff<V, E, VP, EP>>,
+    ) -> Result<(), GraphError> {
+        let dropped_edge = match diff {
+            GraphDiff::UpdateEdgeData(diff) => Some(diff.index),
+            GraphDiff::PatchEdgeData(diff) => Some(diff.index),
+            GraphDiff::RemoveEdge(diff) => Some(diff.edge_index),
+            _ => None,
+        }
+        .is_some_and(|index| self.dropped_edges.contains(&index));
+
+        if dropped_edge {
+            return Ok(());
+        }
+
+        match diff {
+            GraphDiff::AddVertex(diff) => {
+                self.add_vertex(diff.vertex_index, &diff.vertex_data, out);
+            }
+            GraphDiff::AddEdge(diff) => {
+                self.add_edge(diff.edge_index, diff.from, diff.to, &diff.edge_data, out)?;
+            }
+            GraphDiff::RemoveEdge(diff) => self.remove_edge(diff.edge_index, out)?,
+            GraphDiff::RemoveVertex(diff) => self.remove_vertex(diff.vertex_index, out)?,
+            GraphDiff::UpdateVertexData(diff) => {
+                self.update_vertex(diff.index, &diff.after, out)?
+            }
+            GraphDiff::UpdateEdgeData(diff) => self.update_edge(diff.index, &diff.after, out)?,
+            GraphDiff::PatchVertexData(diff) => {
+                let index = self.vertex(diff.index);
+
+                if self.check_update(GraphIndex::Vertex(diff.index), GraphIndex::Vertex(index)) {
+                    let patch = GraphDiff::PatchVertexData(PatchVertexData {
+                        index,
+                        ..diff.clone()
+                    });
+                    self.apply(patch, out)?;
+                }
+            }
+            GraphDiff::PatchEdgeData(diff) => {
+                let index = self.edge(diff.index);
+
+                if self.check_update(GraphIndex::Edge(diff.index), GraphIndex::Edge(index)) {
+                    let patch = GraphDiff::PatchEdgeData(PatchEdgeData {
+                        index,
+                        ..diff.clone()
+                    });
+                    self.apply(patch, out)?;
+                }
+            }
+            GraphDiff::Group(diffs) => {
+                for diff in diffs {
+                    self.rebase_diff(diff, out)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_vertex(
+        &mut self,
+        old_index: VertexIndex,
+        data: &V,
+        out: &mut Vec<GraphDiff<V, E, VP, EP>>,
+    ) {
+        let (index, diff) = self.graph.add_vertex(data.clone());
+        self.rebase.vertex_map.insert(old_index, index);
+        out.push(diff.with_patches());
+    }
+
+    fn update_vertex(
+        &mut self,
+        old_index: VertexIndex,
+        value: &V,
+        out: &mut Vec<GraphDiff<V, E, VP, EP>>,
+    ) -> Result<(), GraphError> {
+        let index = self.vertex(old_index);
+
+        if self.graph.get_vertex(index).is_none() {
+            let conflict = RebaseConflict::UpdatedRemoved {
+                index: GraphIndex::Vertex(old_index),
+            };
+
+            if self.keep_removed(conflict) {
+                self.add_vertex(old_index, value, out);
+            }
+
+            return Ok(());
+        }
+
+        let remote_base = self
+            .base
+            .get_vertex_data(old_index)
+            .filter(|_| self.remote_updated(GraphIndex::Vertex(old_index)));
+
+        let value = if let Some(base) = remote_base {
+            self.rebase.conflicts.push(RebaseConflict::BothUpdated {
+                index: GraphIndex::Vertex(old_index),
+            });
+
+            match self
+                .resolver
+                .resolve_vertex_data(base, &self.graph[index].data, value)
+            {
+                Resolution::Local => value.clone(),
+                Resolution::Remote => return Ok(()),
+                Resolution::Value(value) => value,
+            }
+        } else {
+            value.clone()
+        };
+
+        let (_, diff) = self.graph.update_vertex(index, value)?;
+        out.push(diff.with_patches());
+
+        Ok(())
+    }
+
+    fn update_edge(
+        &mut self,
+        old_index: EdgeIndex,
+        value: &E,
+        out: &mut Vec<GraphDiff<V, E, VP, EP>>,
+    ) -> Result<(), GraphError> {
+        let index = self.edge(old_index);
+
+        if self.graph.get_edge(index).is_none() {
+            let conflict = RebaseConflict::UpdatedRemoved {
+                index: GraphIndex::Edge(old_index),
+            };
+
+            // edges the local side added don't have endpoints to go back to
+            if let (true, Some(edge)) = (self.keep_removed(conflict), self.base.get_edge(old_index))
+            {
+                let (from, to) = (self.vertex(edge.from), self.vertex(edge.to));
+
+                if self.graph.get_vertex(from).is_some() && self.graph.get_vertex(to).is_some() {
+                    let (index, diff) = self.graph.add_edge(from, to, value.clone())?;
+                    self.rebase.edge_map.insert(old_index, index);
+                    out.push(diff.with_patches());
+                }
+            }
+
+            return Ok(());
+        }
+
+        let remote_base = self
+            .base
+            .get_edge_data(old_index)
+            .filter(|_| self.remote_updated(GraphIndex::Edge(old_index)));
+
+        let value = if let Some(base) = remote_base {
+            self.rebase.conflicts.push(RebaseConflict::BothUpdated {
+                index: GraphIndex::Edge(old_index),
+            });
+
+            match self
+                .resolver
+                .resolve_edge_data(base, &self.graph[index].data, value)
+            {
+                Resolution::Local => value.clone(),
+                Resolution::Remote => return Ok(()),
+                Resolution::Value(value) => value,
+            }
+        } else {
+            value.clone()
+        };
+
+        let (_, diff) = self.graph.update_edge(index, value)?;
+        out.push(diff.with_patches());
+
+        Ok(())
+    }
+
+    fn add_edge(
+        &mut self,
+        old_index: EdgeIndex,
+        from: VertexIndex,
+        to: VertexIndex,
+        data: &E,
+        out: &mut Vec<GraphDiff<V, E, VP, EP>>,
+    ) -> Result<(), GraphError> {
+        for endpoint in [from, to] {
+            if self.graph.get_vertex(self.vertex(endpoint)).is_none() {
+                let conflict = RebaseConflict::EndpointRemoved {
+                    edge: old_index,
+                    vertex: endpoint,
+                };
+
+                let keep = self.keep_removed(conflict);
+                let Some(data) = self.base.get_vertex_data(endpoint).filter(|_| keep) else {
+                    self.dropped_edges.insert(old_index);
+
+                    return Ok(());
+                };
+
+                let (index, diff) = self.graph.add_vertex(data.clone());
+                self.rebase.vertex_map.insert(endpoint, index);
+                out.push(diff.with_patches());
+            }
+        }
+
+        let (index, diff) =
+            self.graph
+                .add_edge(self.vertex(from), self.vertex(to), data.clone())?;
+        self.rebase.edge_map.insert(old_index, index);
+        out.push(diff.with_patches());
+
+        Ok(())
+    }
+
+    fn remove_vertex(
+        &mut self,
+        old_index: VertexIndex,
+        out: &mut Vec<GraphDiff<V, E, VP, EP>>,
+    ) -> Result<(), GraphError> {
+        let index = self.vertex(old_index);
+
+        // both sides removed it
+        if self.graph.get_vertex(index).is_none() {
+            return Ok(());
+        }
+
+        let mut keep = false;
+        if self.remote_updated(GraphIndex::Vertex(old_index)) {
+            keep |= self.keep_removed(RebaseConflict::RemovedUpdated {
+                index: GraphIndex::Vertex(old_index),
+            });
+        }
+
+        // edges the remote side added would be removed with it
+        for edge in self.graph[index].get_connected_edges() {
+            let local = self.rebase.edge_map.values().any(|local| *local == edge);
+
+            if !local && self.base.get_edge(edge).is_none() {
+                keep |= self.keep_removed(RebaseConflict::RemovedConnected {
+                    vertex: old_index,
+                    edge,
+                });
+            }
+        }
+
+        if !keep {
+            out.push(self.graph.remove_vertex(index)?.with_patches());
+        }
+
+        Ok(())
+    }
+
+    fn remove_edge(
+        &mut self,
+        old_index: EdgeIndex,
+        out: &mut Vec<GraphDiff<V, E, VP, EP>>,
+    ) -> Result<(), GraphError> {
+        let index = self.edge(old_index);
+
+        // both sides removed it
+        if self.graph.get_edge(index).is_none() {
+            return Ok(());
+        }
+
+        let keep = self.remote_updated(GraphIndex::Edge(old_index))
+            && self.keep_removed(RebaseConflict::RemovedUpdated {
+                index: GraphIndex::Edge(old_index),
+            });
+
+        if !keep {
+            out.push(self.graph.remove_edge(index)?.with_patches());
+        }
+
+        Ok(())
+    }
+
+    /// Record the conflict, and ask the resolver whether to keep what was
+    /// removed.
+    fn keep_removed(&mut self, conflict: RebaseConflict) -> bool {
+        let keep = self.resolver.keep_removed(&conflict);
+        self.rebase.conflicts.push(conflict);
+
+        keep
+    }
+
+    /// Record any conflict with a patch of `old_index` (now at `index`), and
+    /// whether the patch can still be applied.
+    fn check_update(&mut self, old_index: GraphIndex, index: GraphIndex) -> bool {
+        let exists = match index {
+            GraphIndex::Vertex(index) => self.graph.get_vertex(index).is_some(),
+            GraphIndex::Edge(index) => self.graph.get_edge(index).is_some(),
+        };
+
+        if !exists {
+            self.rebase
+                .conflicts
+                .push(RebaseConflict::UpdatedRemoved { index: old_index });
+        } else if self.remote_updated(old_index) {
+            self.rebase
+                .conflicts
+                .push(RebaseConflict::BothUpdated { index: old_index });
+        }
+
+        exists
+    }
+
+    fn apply(
+        &mut self,
+        diff: GraphDiff<V, E, VP, EP>,
+        out: &mut Vec<GraphDiff<V, E, VP, EP>>,
+    ) -> Result<(), GraphError> {
+        self.graph.apply_diff_ref(&diff)?;
+        out.push(diff);
+
+        Ok(())
+    }
+
+    /// Only vertices and edges from the base graph can have been updated
+    /// remotely; ones the local side added may share their index with ones
+    /// the remote side added.
+    fn remote_updated(&self, old_index: GraphIndex) -> bool {
+        let local = match old_index {
+            GraphIndex::Vertex(index) => self.rebase.vertex_map.contains_key(&index),
+            GraphIndex::Edge(index) => self.rebase.edge_map.contains_key(&index),
+        };
+
+        !local && self.remote_updated.contains(&old_index)
+    }
+
+    fn vertex(&self, index: VertexIndex) -> VertexIndex {
+        self.rebase.map_vertex(index)
+    }
+
+    fn edge(&self, index: EdgeIndex) -> EdgeIndex {
+        self.rebase.map_edge(index)
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{Graph, GraphDiff, GraphIndex, RebaseConflict};
+
+#[test]
+fn test_rebase_remaps_added_indices() {
+    let mut base: Graph<i32, i32> = Graph::new();
+    let shared = base.add_vertex_untracked(1);
+
+    let mut local_graph = base.clone();
+    let (local_vertex, add_vertex) = local_graph.add_vertex(2);
+    let (local_edge, add_edge) = local_graph.add_edge(shared, local_vertex, 10).unwrap();
+    let local = [add_vertex, add_edge];
+
+    let mut remote_graph = base.clone();
+    let (remote_vertex, add_remote) = remote_graph.add_vertex(3);
+    let (_, update) = remote_graph.update_vertex(shared, 5).unwrap();
+    let remote = [add_remote, update];
+
+    // both sides allocated the same slot
+    assert_eq!(local_vertex, remote_vertex);
+
+    let rebase = base.rebase(&local, &remote).unwrap();
+    assert!(rebase.get_conflicts().is_empty());
+    assert_eq!(rebase.get_diffs().len(), 2);
+
+    remote_graph
+        .apply_diffs(rebase.get_diffs().to_vec())
+        .unwrap();
+
+    let rebased_vertex = rebase.map_vertex(local_vertex);
+    assert_ne!(rebased_vertex, remote_vertex);
+    assert_eq!(rebase.map_vertex(shared), shared);
+    assert_eq!(*remote_graph.get_vertex_data(shared).unwrap(), 5);
+    assert_eq!(*remote_graph.get_vertex_data(remote_vertex).unwrap(), 3);
+    assert_eq!(*remote_graph.get_vertex_data(rebased_vertex).unwrap(), 2);
+
+    let rebased_edge = remote_graph.get_edge(rebase.map_edge(local_edge)).unwrap();
+    assert_eq!(rebased_edge.get_from(), shared);
+    assert_eq!(rebased_edge.get_to(), rebased_vertex);
+}
+
+#[test]
+fn test_rebase_conflicts() {
+    let mut base: Graph<i32, i32> = Graph::new();
+    let first = base.add_vertex_untracked(1);
+    let second = base.add_vertex_untracked(2);
+    let edge = base.add_edge_untracked(first, second, 10).unwrap();
+
+    let mut remote_graph = base.clone();
+    let (_, update) = remote_graph.update_vertex(first, 5).unwrap();
+    let remove = remote_graph.remove_vertex(second).unwrap();
+    let remote = [update, remove];
+
+    let mut local_graph = base.clone();
+    let (_, update_first) = local_graph.update_vertex(first, 20).unwrap();
+    let (_, update_second) = local_graph.update_vertex(second, 30).unwrap();
+    let remove_edge = local_graph.remove_edge(edge).unwrap();
+    let (new_edge, add_edge) = local_graph.add_edge(first, second, 40).unwrap();
+    let local = [update_first, update_second, remove_edge, add_edge];
+
+    let rebase = base.rebase(&local, &remote).unwrap();
+    assert_eq!(
+        rebase.get_conflicts(),
+        [
+            RebaseConflict::BothUpdated {
+                index: GraphIndex::Vertex(first)
+            },
+            RebaseConflict::UpdatedRemoved {
+                index: GraphIndex::Vertex(second)
+            },
+            RebaseConflict::EndpointRemoved {
+                edge: new_edge,
+                vertex: second
+            },
+        ]
+    );
+
+    // everything but the first update touched removed elements
+    let diffs = rebase.into_diffs();
+    assert!(matches!(&diffs[0], GraphDiff::UpdateVertexData(diff) if *diff.get_before() == 5));
+    assert!(diffs[1..]
+        .iter()
+        .all(|diff| matches!(diff, GraphDiff::Group(diffs) if diffs.is_empty())));
+
+    remote_graph.apply_diffs(diffs).unwrap();
+    assert_eq!(*remote_graph.get_vertex_data(first).unwrap(), 20);
+    assert_eq!(remote_graph.edge_indexes().count(), 0);
+}
+
+#[test]
+fn test_rebase_skips_changes_to_dropped_edges() {
+    let mut base: Graph<i32, i32> = Graph::new();
+    let first = base.add_vertex_untracked(1);
+    let second = base.add_vertex_untracked(2);
+
+    let mut local_graph = base.clone();
+    let (local_edge, add_edge) = local_graph.add_edge(first, second, 10).unwrap();
+    let (_, update_edge) = local_graph.update_edge(local_edge, 20).unwrap();
+    let remove_edge = local_graph.remove_edge(local_edge).unwrap();
+    let local = [add_edge, update_edge, remove_edge];
+
+    let mut remote_graph = base.clone();
+    let remove = remote_graph.remove_vertex(second).unwrap();
+    let (remote_edge, add_remote) = remote_graph.add_edge(first, first, 30).unwrap();
+    let remote = [remove, add_remote];
+
+    // both sides allocated the same slot
+    assert_eq!(local_edge, remote_edge);
+
+    let rebase = base.rebase(&local, &remote).unwrap();
+    assert_eq!(
+        rebase.get_conflicts(),
+        [RebaseConflict::EndpointRemoved {
+            edge: local_edge,
+            vertex: second
+        }]
+    );
+    assert!(rebase
+        .get_diffs()
+        .iter()
+        .all(|diff| matches!(diff, GraphDiff::Group(diffs) if diffs.is_empty())));
+
+    // the remote edge in the same slot is left alone
+    remote_graph
+        .apply_diffs(rebase.get_diffs().to_vec())
+        .unwrap();
+    assert_eq!(*remote_graph.get_edge_data(remote_edge).unwrap(), 30);
+}
@@ -0,0 +1,149 @@
+use crate::{
+    errors::GraphError,
+    graph::{EdgeIndex, VertexIndex},
+    graph_diff::GraphDiff,
+    patch::{NoPatch, Patch, Patchable},
+};
+
+pub(crate) mod sealed {
+    use crate::{graph::Graph, graph_diff::GraphDiff};
+
+    /// What a [`super::Recorder`] needs: the graph it changes, and somewhere
+    /// to keep the diffs.
+    pub trait Record<V, E, VP, EP> {
+        fn recorded_graph(&mut self) -> &mut Graph<V, E>;
+
+        /// Keep a diff that was just applied to the graph.
+        fn record(&mut self, diff: GraphDiff<V, E, VP, EP>);
+    }
+}
+
+/// Changes to a graph that are recorded as diffs as they're made, shared by
+/// [`crate::History`], [`crate::UndoTree`] and [`crate::Transaction`]. Each
+/// change is applied straight away, and recorded as a single diff.
+pub trait Recorder<V: Clone, E: Clone, VP: Patch<V> = NoPatch, EP: Patch<E> = NoPatch>:
+    sealed::Record<V, E, VP, EP>
+{
+    fn add_vertex(&mut self, vertex_data: V) -> VertexIndex {
+        let (index, diff) = self.recorded_graph().add_vertex(vertex_data);
+        self.record(diff.with_patches());
+
+        index
+    }
+
+    fn add_edge(
+        &mut self,
+        from_index: VertexIndex,
+        to_index: VertexIndex,
+        edge_data: E,
+    ) -> Result<EdgeIndex, GraphError> {
+        let (index, diff) = self
+            .recorded_graph()
+            .add_edge(from_index, to_index, edge_data)?;
+        self.record(diff.with_patches());
+
+        Ok(index)
+    }
+
+    fn update_vertex(&mut self, index: VertexIndex, value: V) -> Result<V, GraphError> {
+        let (old_value, diff) = self.recorded_graph().update_vertex(index, value)?;
+        self.record(diff.with_patches());
+
+        Ok(old_value)
+    }
+
+    fn update_edge(&mut self, index: EdgeIndex, value: E) -> Result<E, GraphError> {
+        let (old_value, diff) = self.recorded_graph().update_edge(index, value)?;
+        self.record(diff.with_patches());
+
+        Ok(old_value)
+    }
+
+    fn update_vertex_with<T, F>(&mut self, index: VertexIndex, f: F) -> Result<T, GraphError>
+    where
+        F: FnOnce(&mut V) -> T,
+    {
+        let (result, diff) = self.recorded_graph().update_vertex_with(index, f)?;
+        self.record(diff.with_patches());
+
+        Ok(result)
+    }
+
+    fn update_edge_with<T, F>(&mut self, index: EdgeIndex, f: F) -> Result<T, GraphError>
+    where
+        F: FnOnce(&mut E) -> T,
+    {
+        let (result, diff) = self.recorded_graph().update_edge_with(index, f)?;
+        self.record(diff.with_patches());
+
+        Ok(result)
+    }
+
+    fn remove_vertex(&mut self, index: VertexIndex) -> Result<V, GraphError> {
+        let diff = self.recorded_graph().remove_vertex_internal(index)?;
+        let vertex_data = diff.vertex.data.clone();
+        self.record(GraphDiff::RemoveVertex(diff));
+
+        Ok(vertex_data)
+    }
+
+    fn remove_edge(&mut self, index: EdgeIndex) -> Result<E, GraphError> {
+        let diff = self.recorded_graph().remove_edge_internal(index)?;
+        let edge_data = diff.edge.data.clone();
+        self.record(GraphDiff::RemoveEdge(diff));
+
+        Ok(edge_data)
+    }
+
+    fn patch_vertex(&mut self, index: VertexIndex, patch: VP) -> Result<(), GraphError>
+    where
+        V: Patchable<Patch = VP>,
+    {
+        let diff = self.recorded_graph().patch_vertex(index, patch)?;
+        self.record(diff);
+
+        Ok(())
+    }
+
+    fn patch_vertex_with<T, F>(&mut self, index: VertexIndex, f: F) -> Result<T, GraphError>
+    where
+        V: Patchable<Patch = VP>,
+        F: FnOnce(&mut V) -> T,
+    {
+        let (result, diff) = self.recorded_graph().patch_vertex_with(index, f)?;
+        self.record(diff);
+
+        Ok(result)
+    }
+
+    fn patch_edge(&mut self, index: EdgeIndex, patch: EP) -> Result<(), GraphError>
+    where
+        E: Patchable<Patch = EP>,
+    {
+        let diff = self.recorded_graph().patch_edge(index, patch)?;
+        self.record(diff);
+
+        Ok(())
+    }
+
+    fn patch_edge_with<T, F>(&mut self, index: EdgeIndex, f: F) -> Result<T, GraphError>
+    where
+        E: Patchable<Patch = EP>,
+        F: FnOnce(&mut E) -> T,
+    {
+        let (result, diff) = self.recorded_graph().patch_edge_with(index, f)?;
+        self.record(diff);
+
+        Ok(result)
+    }
+}
+
+impl<T, V, E, VP, EP> Recorder<V, E, VP, EP> for T
+where
+    T: sealed::Record<V, E, VP, EP>,
+    V: Clone,
+    E: Clone,
+    VP: Patch<V>,
+    EP: Patch<E>,
+{
+}
@@ -0,0 +1,566 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec::Vec,
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::ReplicatedError,
+    graph::{EdgeIndex, Graph, VertexIndex},
+    graph_diff::GraphDiff,
+};
+
+/// Identifies a replica. Every replica taking part in a session needs a
+/// different one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ReplicaId(pub u64);
+
+/// Identifies an operation across all replicas. Ordered by Lamport clock,
+/// then replica, which decides which of two concurrent updates wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OperationId {
+    clock: u64,
+    replica: ReplicaId,
+}
+
+impl OperationId {
+    pub fn get_clock(&self) -> u64 {
+        self.clock
+    }
+
+    pub fn get_replica(&self) -> ReplicaId {
+        self.replica
+    }
+}
+
+/// A vertex's identity on every replica: the operation that added it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct VertexId(pub(crate) OperationId);
+
+/// An edge's identity on every replica: the operation that added it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EdgeId(pub(crate) OperationId);
+
+/// What happens when a vertex is removed while another replica concurrently
+/// connects an edge to it. Every replica in a session has to use the same
+/// policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// The vertex is kept (or brought back) for the new edge.
+    AddWins,
+    /// The vertex stays removed, so the new edge is never shown.
+    RemoveWins,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "js_names", serde(tag = "variant", content = "data"))]
+pub enum OperationKind<V, E> {
+    AddVertex {
+        data: V,
+    },
+    AddEdge {
+        from: VertexId,
+        to: VertexId,
+        data: E,
+    },
+    /// `edges` are the edges connected to the vertex when it was removed,
+    /// which are removed with it.
+    RemoveVertex {
+        vertex: VertexId,
+        edges: Vec<EdgeId>,
+    },
+    RemoveEdge {
+        edge: EdgeId,
+    },
+    UpdateVertex {
+        vertex: VertexId,
+        data: V,
+    },
+    UpdateEdge {
+        edge: EdgeId,
+        data: E,
+    },
+}
+
+/// One entry in a replica's operation log, to be sent to other replicas.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Operation<V, E> {
+    id: OperationId,
+    kind: OperationKind<V, E>,
+}
+
+impl<V, E> Operation<V, E> {
+    pub fn get_id(&self) -> OperationId {
+        self.id
+    }
+
+    pub fn get_kind(&self) -> &OperationKind<V, E> {
+        &self.kind
+    }
+}
+
+#[derive(Debug, Clone)]
+struct VertexState<V> {
+    added: bool,
+    removed: bool,
+    /// Last-writer-wins register
+    data: Option<(OperationId, V)>,
+    /// Every edge connected to it, including removed ones
+    edges: BTreeSet<EdgeId>,
+}
+
+#[derive(Debug, Clone)]
+struct EdgeState<E> {
+    endpoints: Option<(VertexId, VertexId)>,
+    removed: bool,
+    /// Last-writer-wins register
+    data: Option<(OperationId, E)>,
+}
+
+/// A [`Graph`] that can be edited on several replicas at once and merged in
+/// any order, always ending up the same (a CRDT).
+///
+/// Vertices and edges are identified by [`VertexId`]s and [`EdgeId`]s that
+/// are unique across replicas. Removing something always beats updating it,
+/// concurrent updates are decided by last writer wins, and a vertex removed
+/// while an edge is connected to it is decided by the [`ConflictPolicy`].
+///
+/// Every change, local or merged, is made to the wrapped graph through
+/// regular [`GraphDiff`]s, which are returned so they can be passed on to
+/// anything else watching the graph.
+///
+/// The operation log grows with every change, so other replicas can catch up
+/// from it. Once every replica has merged the oldest operations, they can be
+/// dropped with [`ReplicatedGraph::compact`]. Removed vertices and edges are
+/// still remembered, so late operations on them can be ignored.
+#[derive(Debug, Clone)]
+pub struct ReplicatedGraph<V, E> {
+    graph: Graph<V, E>,
+    replica: ReplicaId,
+    policy: ConflictPolicy,
+    clock: u64,
+    /// Operations up to this clock were compacted away
+    stable_clock: u64,
+    operations: Vec<Operation<V, E>>,
+    seen: BTreeSet<OperationId>,
+    verticies: BTreeMap<VertexId, VertexState<V>>,
+    edges: BTreeMap<EdgeId, EdgeState<E>>,
+    /// What's currently in the graph, and which write its data is from
+    vertex_indexes: BTreeMap<VertexId, (VertexIndex, OperationId)>,
+    edge_indexes: BTreeMap<EdgeId, (EdgeIndex, OperationId)>,
+    vertex_ids: BTreeMap<VertexIndex, VertexId>,
+    edge_ids: BTreeMap<EdgeIndex, EdgeId>,
+}
+
+impl<V: Clone, E: Clone> ReplicatedGraph<V, E> {
+    pub fn new(replica: ReplicaId, policy: ConflictPolicy) -> ReplicatedGraph<V, E> {
+        ReplicatedGraph {
+            graph: Graph::new(),
+            replica,
+            policy,
+            clock: 0,
+            stable_clock: 0,
+            operations: Vec::new(),
+            seen: BTreeSet::new(),
+            verticies: BTreeMap::new(),
+            edges: BTreeMap::new(),
+            vertex_indexes: BTreeMap::new(),
+            edge_indexes: BTreeMap::new(),
+            vertex_ids: BTreeMap::new(),
+            edge_ids: BTreeMap::new(),
+        }
+    }
+
+    pub fn graph(&self) -> &Graph<V, E> {
+        &self.graph
+    }
+
+    pub fn replica(&self) -> ReplicaId {
+        self.replica
+    }
+
+    /// The Lamport clock of the newest operation this replica knows about.
+    pub fn clock(&self) -> u64 {
+        self.clock
+    }
+
+    /// Every operation this replica knows about, local or merged, in the order
+    /// they were received, minus any that were compacted away.
+    pub fn operations(&self) -> &[Operation<V, E>] {
+        &self.operations
+    }
+
+    pub fn get_vertex_index(&self, id: VertexId) -> Option<VertexIndex> {
+        self.vertex_indexes.get(&id).map(|(index, _)| *index)
+    }
+
+    pub fn get_edge_index(&self, id: EdgeId) -> Option<EdgeIndex> {
+        self.edge_indexes.get(&id).map(|(index, _)| *index)
+    }
+
+    pub fn get_vertex_id(&self, index: VertexIndex) -> Option<VertexId> {
+        self.vertex_ids.get(&index).copied()
+    }
+
+    pub fn get_edge_id(&self, index: EdgeIndex) -> Option<EdgeId> {
+        self.edge_ids.get(&index).copied()
+    }
+
+    /// Returns the new vertex's id, and the changes made to the graph as a
+    /// group.
+    pub fn add_vertex(&mut self, data: V) -> (VertexId, GraphDiff<V, E>) {
+        let id = self.next_id();
+        let diff = self.perform(id, OperationKind::AddVertex { data });
+
+        (VertexId(id), diff)
+    }
+
+    /// Returns the new edge's id, and the changes made to the graph as a
+    /// group.
+    pub fn add_edge(
+        &mut self,
+        from: VertexId,
+        to: VertexId,
+        data: E,
+    ) -> Result<(EdgeId, GraphDiff<V, E>), ReplicatedError> {
+        self.assert_vertex_exists(from)?;
+        self.assert_vertex_exists(to)?;
+
+        let id = self.next_id();
+        let diff = self.perform(id, OperationKind::AddEdge { from, to, data });
+
+        Ok((EdgeId(id), diff))
+    }
+
+    /// Remove a vertex and the edges connected to it, returning the changes
+    /// made to the graph as a group.
+    pub fn remove_vertex(&mut self, vertex: VertexId) -> Result<GraphDiff<V, E>, ReplicatedError> {
+        self.assert_vertex_exists(vertex)?;
+
+        let edges = self
+            .incident_edges(vertex)
+            .filter(|edge| !self.edges[edge].removed)
+            .collect();
+
+        let id = self.next_id();
+        Ok(self.perform(id, OperationKind::RemoveVertex { vertex, edges }))
+    }
+
+    pub fn remove_edge(&mut self, edge: EdgeId) -> Result<GraphDiff<V, E>, ReplicatedError> {
+        self.assert_edge_exists(edge)?;
+
+        let id = self.next_id();
+        Ok(self.perform(id, OperationKind::RemoveEdge { edge }))
+    }
+
+    pub fn update_vertex(
+        &mut self,
+        vertex: VertexId,
+        data: V,
+    ) -> Result<GraphDiff<V, E>, ReplicatedError> {
+        self.assert_vertex_exists(vertex)?;
+
+        let id = self.next_id();
+        Ok(self.perform(id, OperationKind::UpdateVertex { vertex, data }))
+    }
+
+    pub fn update_edge(
+        &mut self,
+        edge: EdgeId,
+        data: E,
+    ) -> Result<GraphDiff<V, E>, ReplicatedError> {
+        self.assert_edge_exists(edge)?;
+
+        let id = self.next_id();
+        Ok(self.perform(id, OperationKind::UpdateEdge { edge, data }))
+    }
+
+    /// Merge operations from another replica, in any order and with any
+    /// duplicates. Returns the changes made to the graph as a group.
+    pub fn merge<'a, I>(&mut self, operations: I) -> GraphDiff<V, E>
+    where
+        I: IntoIterator<Item = &'a Operation<V, E>>,
+        V: 'a,
+        E: 'a,
+    {
+        let mut affected = Affected::default();
+
+        for operation in operations {
+            // anything up to the stable clock has already been merged
+            if operation.id.clock > self.stable_clock && self.seen.insert(operation.id) {
+                self.clock = self.clock.max(operation.id.clock);
+                self.integrate(operation, &mut affected);
+                self.operations.push(operation.clone());
+            }
+        }
+
+        GraphDiff::Group(self.reconcile(affected))
+    }
+
+    /// Drop every operation with a clock of at most `clock` from the log.
+    ///
+    /// Only do this once they're causally stable: every replica has merged
+    /// all of them, and moved its own clock past `clock` (see
+    /// [`ReplicatedGraph::clock`]). A replica that hasn't can't catch up from
+    /// this one's log anymore, and operations it sends that are that old are
+    /// ignored.
+    pub fn compact(&mut self, clock: u64) {
+        self.stable_clock = self.stable_clock.max(clock.min(self.clock));
+
+        let stable_clock = self.stable_clock;
+        self.operations
+            .retain(|operation| operation.id.clock > stable_clock);
+        self.seen.retain(|id| id.clock > stable_clock);
+    }
+
+    /// Merge everything another replica knows about.
+    pub fn merge_from(&mut self, other: &ReplicatedGraph<V, E>) -> GraphDiff<V, E> {
+        self.merge(other.operations())
+    }
+
+    fn next_id(&mut self) -> OperationId {
+        self.clock += 1;
+
+        OperationId {
+            clock: self.clock,
+            replica: self.replica,
+        }
+    }
+
+    fn perform(&mut self, id: OperationId, kind: OperationKind<V, E>) -> GraphDiff<V, E> {
+        let operation = Operation { id, kind };
+        let mut affected = Affected::default();
+
+        self.seen.insert(id);
+        self.integrate(&operation, &mut affected);
+        self.operations.push(operation);
+
+        GraphDiff::Group(self.reconcile(affected))
+    }
+
+    /// Update the replicated state. This only ever moves towards "more
+    /// removed" and "newer data", so the order operations arrive in doesn't
+    /// matter.
+    fn integrate(&mut self, operation: &Operation<V, E>, affected: &mut Affected) {
+        let id = operation.id;
+
+        match &operation.kind {
+            OperationKind::AddVertex { data } => {
+                let vertex = self.vertex_state(VertexId(id));
+                vertex.added = true;
+                write_register(&mut vertex.data, id, data);
+                affected.verticies.insert(VertexId(id));
+            }
+            OperationKind::AddEdge { from, to, data } => {
+                let edge = self.edge_state(EdgeId(id));
+                edge.endpoints = Some((*from, *to));
+                write_register(&mut edge.data, id, data);
+                self.vertex_state(*from).edges.insert(EdgeId(id));
+                self.vertex_state(*to).edges.insert(EdgeId(id));
+                affected.edges.insert(EdgeId(id));
+                affected.verticies.extend([*from, *to]);
+            }
+            OperationKind::RemoveVertex { vertex, edges } => {
+                self.vertex_state(*vertex).removed = true;
+                affected.verticies.insert(*vertex);
+
+                for edge in edges {
+                    self.remove_edge_state(*edge, affected);
+                }
+            }
+            OperationKind::RemoveEdge { edge } => self.remove_edge_state(*edge, affected),
+            OperationKind::UpdateVertex { vertex, data } => {
+                write_register(&mut self.vertex_state(*vertex).data, id, data);
+                affected.verticies.insert(*vertex);
+            }
+            OperationKind::UpdateEdge { edge, data } => {
+                write_register(&mut self.edge_state(*edge).data, id, data);
+                affected.edges.insert(*edge);
+            }
+        }
+    }
+
+    fn remove_edge_state(&mut self, id: EdgeId, affected: &mut Affected) {
+        let edge = self.edge_state(id);
+        edge.removed = true;
+
+        if let Some((from, to)) = edge.endpoints {
+            affected.verticies.extend([from, to]);
+        }
+        affected.edges.insert(id);
+    }
+
+    /// Bring the graph in line with the replicated state for everything that
+    /// was affected, returning the diffs that were applied.
+    fn reconcile(&mut self, mut affected: Affected) -> Vec<GraphDiff<V, E>> {
+        // whether edges are shown depends on their endpoints
+        for vertex in affected.verticies.iter() {
+            if self.vertex_visible(*vertex) != self.vertex_indexes.contains_key(vertex) {
+                affected.edges.extend(self.incident_edges(*vertex));
+            }
+        }
+
+        let mut diffs = Vec::new();
+
+        // remove edges first, so removing verticies doesn't take edges with it
+        for edge in affected.edges.iter() {
+            if !self.edge_visible(*edge) {
+                if let Some((index, _)) = self.edge_indexes.remove(edge) {
+                    self.edge_ids.remove(&index);
+                    diffs.push(self.graph.remove_edge(index).expect(OUT_OF_SYNC));
+                }
+            }
+        }
+
+        for vertex in affected.verticies.iter() {
+            if !self.vertex_visible(*vertex) {
+                if let Some((index, _)) = self.vertex_indexes.remove(vertex) {
+                    self.vertex_ids.remove(&index);
+                    diffs.push(self.graph.remove_vertex(index).expect(OUT_OF_SYNC));
+                }
+            }
+        }
+
+        for vertex in affected.verticies.iter() {
+            if !self.vertex_visible(*vertex) {
+                continue;
+            }
+
+            let (data_id, data) = self.verticies[vertex].data.clone().unwrap();
+            match self.vertex_indexes.get_mut(vertex) {
+                Some((index, shown)) if *shown != data_id => {
+                    *shown = data_id;
+                    let (_, diff) = self.graph.update_vertex(*index, data).expect(OUT_OF_SYNC);
+                    diffs.push(diff);
+                }
+                Some(_) => {}
+                None => {
+                    let (index, diff) = self.graph.add_vertex(data);
+                    self.vertex_indexes.insert(*vertex, (index, data_id));
+                    self.vertex_ids.insert(index, *vertex);
+                    diffs.push(diff);
+                }
+            }
+        }
+
+        for edge in affected.edges.iter() {
+            if !self.edge_visible(*edge) {
+                continue;
+            }
+
+            let state = &self.edges[edge];
+            let (from, to) = state.endpoints.unwrap();
+            let (data_id, data) = state.data.clone().unwrap();
+
+            match self.edge_indexes.get_mut(edge) {
+                Some((index, shown)) if *shown != data_id => {
+                    *shown = data_id;
+                    let (_, diff) = self.graph.update_edge(*index, data).expect(OUT_OF_SYNC);
+                    diffs.push(diff);
+                }
+                Some(_) => {}
+                None => {
+                    let (index, diff) = self
+                        .graph
+                        .add_edge(
+                            self.vertex_indexes[&from].0,
+                            self.vertex_indexes[&to].0,
+                            data,
+                        )
+                        .expect(OUT_OF_SYNC);
+                    self.edge_indexes.insert(*edge, (index, data_id));
+                    self.edge_ids.insert(index, *edge);
+                    diffs.push(diff);
+                }
+            }
+        }
+
+        diffs
+    }
+
+    fn vertex_visible(&self, id: VertexId) -> bool {
+        let vertex = match self.verticies.get(&id) {
+            Some(vertex) if vertex.added => vertex,
+            _ => return false,
+        };
+
+        !vertex.removed
+            || (self.policy == ConflictPolicy::AddWins
+                && vertex.edges.iter().any(|edge| !self.edges[edge].removed))
+    }
+
+    fn edge_visible(&self, id: EdgeId) -> bool {
+        match self.edges.get(&id) {
+            Some(EdgeState {
+                endpoints: Some((from, to)),
+                removed: false,
+                ..
+            }) => self.vertex_visible(*from) && self.vertex_visible(*to),
+            _ => false,
+        }
+    }
+
+    /// Every edge connected to a vertex, including removed ones.
+    fn incident_edges(&self, id: VertexId) -> impl Iterator<Item = EdgeId> + '_ {
+        self.verticies
+            .get(&id)
+            .into_iter()
+            .flat_map(|vertex| vertex.edges.iter().copied())
+    }
+
+    fn vertex_state(&mut self, id: VertexId) -> &mut VertexState<V> {
+        self.verticies.entry(id).or_insert(VertexState {
+            added: false,
+            removed: false,
+            data: None,
+            edges: BTreeSet::new(),
+        })
+    }
+
+    fn edge_state(&mut self, id: EdgeId) -> &mut EdgeState<E> {
+        self.edges.entry(id).or_insert(EdgeState {
+            endpoints: None,
+            removed: false,
+            data: None,
+        })
+    }
+
+    fn assert_vertex_exists(&self, id: VertexId) -> Result<(), ReplicatedError> {
+        if self.vertex_indexes.contains_key(&id) {
+            Ok(())
+        } else {
+            Err(ReplicatedError::VertexIdDoesNotExist { id })
+        }
+    }
+
+    fn assert_edge_exists(&self, id: EdgeId) -> Result<(), ReplicatedError> {
+        if self.edge_indexes.contains_key(&id) {
+            Ok(())
+        } else {
+            Err(ReplicatedError::EdgeIdDoesNotExist { id })
+        }
+    }
+}
+
+const OUT_OF_SYNC: &str = "Replicated graph has become out of sync with its state";
+
+/// Everything an operation may have changed the visibility or data of.
+#[derive(Default)]
+struct Affected {
+    verticies: BTreeSet<VertexId>,
+    edges: BTreeSet<EdgeId>,
+}
+
+fn write_register<T: Clone>(register: &mut Option<(OperationId, T)>, id: OperationId, value: &T) {
+    if register.as_ref().map_or(true, |(current, _)| *current < id) {
+        *register = Some((id, value.clone()));
+    }
+}
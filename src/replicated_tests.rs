@@ -0,0 +1,171 @@
+use alloc::vec::Vec;
+
+use crate::{ConflictPolicy, Graph, ReplicaId, ReplicatedError, ReplicatedGraph, VertexId};
+
+fn vertex_data(replica: &ReplicatedGraph<i32, i32>) -> Vec<i32> {
+    let mut data: Vec<i32> = replica
+        .graph()
+        .vertex_data_iter()
+        .map(|(_, data)| *data)
+        .collect();
+    data.sort();
+
+    data
+}
+
+fn edge_data(replica: &ReplicatedGraph<i32, i32>) -> Vec<i32> {
+    let mut data: Vec<i32> = replica
+        .graph()
+        .edge_data_iter()
+        .map(|(_, data)| *data)
+        .collect();
+    data.sort();
+
+    data
+}
+
+fn vertex(replica: &ReplicatedGraph<i32, i32>, id: VertexId) -> Option<i32> {
+    let index = replica.get_vertex_index(id)?;
+
+    replica.graph().get_vertex_data(index).copied()
+}
+
+#[test]
+fn test_replicas_converge() {
+    let mut alice = ReplicatedGraph::new(ReplicaId(1), ConflictPolicy::RemoveWins);
+    let mut bob = ReplicatedGraph::new(ReplicaId(2), ConflictPolicy::RemoveWins);
+
+    let (first, _) = alice.add_vertex(1);
+    let (second, _) = alice.add_vertex(2);
+    alice.add_edge(first, second, 10).unwrap();
+    bob.merge_from(&alice);
+
+    // concurrent edits
+    alice.update_vertex(first, 100).unwrap();
+    let (third, _) = alice.add_vertex(3);
+    alice.add_edge(second, third, 20).unwrap();
+
+    bob.update_vertex(first, 200).unwrap();
+    bob.update_vertex(second, 50).unwrap();
+    let (fourth, _) = bob.add_vertex(4);
+    bob.add_edge(fourth, first, 30).unwrap();
+
+    // merging in reverse order converges too
+    let mut carol = ReplicatedGraph::new(ReplicaId(3), ConflictPolicy::RemoveWins);
+    carol.merge(bob.operations().iter().rev());
+    carol.merge(alice.operations().iter().rev());
+
+    let alice_operations = alice.operations().to_vec();
+    alice.merge_from(&bob);
+    bob.merge(&alice_operations);
+
+    for replica in [&alice, &bob, &carol] {
+        assert_eq!(vertex_data(replica), [3, 4, 50, 200]);
+        assert_eq!(edge_data(replica), [10, 20, 30]);
+        // both updates have the same Lamport clock, so the higher replica wins
+        assert_eq!(vertex(replica, first), Some(200));
+    }
+}
+
+#[test]
+fn test_replicated_remove_policies() {
+    for (policy, expected) in [
+        (ConflictPolicy::AddWins, Some(2)),
+        (ConflictPolicy::RemoveWins, None),
+    ] {
+        let mut alice = ReplicatedGraph::new(ReplicaId(1), policy);
+        let mut bob = ReplicatedGraph::new(ReplicaId(2), policy);
+
+        let (first, _) = alice.add_vertex(1);
+        let (second, _) = alice.add_vertex(2);
+        bob.merge_from(&alice);
+
+        alice.remove_vertex(second).unwrap();
+        bob.add_edge(first, second, 10).unwrap();
+        bob.update_vertex(first, 5).unwrap();
+
+        alice.merge_from(&bob);
+        bob.merge_from(&alice);
+
+        for replica in [&alice, &bob] {
+            assert_eq!(vertex(replica, second), expected);
+            assert_eq!(vertex(replica, first), Some(5));
+            assert_eq!(
+                replica.graph().edge_indexes().count(),
+                expected.iter().count()
+            );
+        }
+    }
+}
+
+#[test]
+fn test_replicated_diffs_mirror_graph() {
+    let mut alice = ReplicatedGraph::new(ReplicaId(1), ConflictPolicy::RemoveWins);
+    let mut bob = ReplicatedGraph::new(ReplicaId(2), ConflictPolicy::RemoveWins);
+    let mut mirror: Graph<i32, i32> = Graph::new();
+
+    let (first, diff) = bob.add_vertex(1);
+    mirror.apply_diff(diff).unwrap();
+    let (second, diff) = bob.add_vertex(2);
+    mirror.apply_diff(diff).unwrap();
+    let (_, diff) = bob.add_edge(first, second, 10).unwrap();
+    mirror.apply_diff(diff).unwrap();
+
+    alice.merge_from(&bob);
+    alice.remove_vertex(first).unwrap();
+    alice.update_vertex(second, 20).unwrap();
+
+    // the removal takes the edge with it
+    let diff = bob.merge_from(&alice);
+    assert_eq!(
+        diff.describe(),
+        "removed 1 vertex, removed 1 edge, updated 1 vertex"
+    );
+    mirror.apply_diff(diff).unwrap();
+
+    assert_eq!(mirror.vertex_indexes().count(), 1);
+    assert_eq!(mirror.edge_indexes().count(), 0);
+    assert_eq!(
+        *mirror
+            .get_vertex_data(bob.get_vertex_index(second).unwrap())
+            .unwrap(),
+        20
+    );
+    assert!(matches!(
+        bob.update_vertex(first, 5),
+        Err(ReplicatedError::VertexIdDoesNotExist { id }) if id == first
+    ));
+}
+
+#[test]
+fn test_replicated_compact() {
+    let mut alice = ReplicatedGraph::new(ReplicaId(1), ConflictPolicy::RemoveWins);
+    let mut bob = ReplicatedGraph::new(ReplicaId(2), ConflictPolicy::RemoveWins);
+
+    let (first, _) = alice.add_vertex(1);
+    let (second, _) = alice.add_vertex(2);
+    alice.add_edge(first, second, 10).unwrap();
+    alice.remove_vertex(second).unwrap();
+    bob.merge_from(&alice);
+
+    // both have seen everything up to here
+    let stable = alice.clock().min(bob.clock());
+    let old_operations = alice.operations().to_vec();
+    alice.compact(stable);
+    bob.compact(stable);
+    assert!(alice.operations().is_empty());
+
+    // merging compacted operations again changes nothing
+    let diff = alice.merge(&old_operations);
+    assert_eq!(diff.describe(), "no changes");
+    assert!(alice.operations().is_empty());
+
+    bob.update_vertex(first, 5).unwrap();
+    alice.merge_from(&bob);
+    assert_eq!(alice.operations().len(), 1);
+
+    for replica in [&alice, &bob] {
+        assert_eq!(vertex_data(replica), [5]);
+        assert!(edge_data(replica).is_empty());
+    }
+}
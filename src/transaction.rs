@@ -0,0 +1,135 @@
+use alloc::vec::Vec;
+
+use crate::{
+    errors::GraphError,
+    graph::Graph,
+    graph_diff::GraphDiff,
+    patch::{NoPatch, Patch},
+    recorder::sealed::Record,
+};
+
+/// A set of changes to a graph that is committed as a single
+/// [`GraphDiff::Group`].
+///
+/// Changes are made through [`crate::Recorder`], and applied to the graph
+/// immediately, so later operations in the transaction can see earlier ones.
+/// If the transaction is aborted (or dropped without being committed), every
+/// change made so far is rolled back.
+///
+/// The transaction borrows the graph mutably, so nothing else can change it
+/// in between, and rolling back its own diffs can't fail. If it does anyway,
+/// the graph was already corrupted. [`Transaction::rollback`] reports that,
+/// [`Transaction::abort`] panics, and dropping the transaction leaves the
+/// graph with its changes still applied (it never panics, as it may already
+/// be unwinding).
+///
+/// `VP` and `EP` are the patch types of its diffs, see [`GraphDiff`].
+#[derive(Debug)]
+pub struct Transaction<'a, V: Clone, E: Clone, VP: Patch<V> = NoPatch, EP: Patch<E> = NoPatch> {
+    graph: &'a mut Graph<V, E>,
+    diffs: Vec<GraphDiff<V, E, VP, EP>>,
+}
+
+impl<'a, V: Clone, E: Clone, VP: Patch<V>, EP: Patch<E>> Transaction<'a, V, E, VP, EP> {
+    pub(crate) fn new(graph: &'a mut Graph<V, E>) -> Transaction<'a, V, E, VP, EP> {
+        Transaction {
+            graph,
+            diffs: Vec::new(),
+        }
+    }
+
+    pub fn graph(&self) -> &Graph<V, E> {
+        self.graph
+    }
+
+    /// Diffs recorded so far, in the order they were made.
+    pub fn diffs(&self) -> &[GraphDiff<V, E, VP, EP>] {
+        &self.diffs
+    }
+
+    /// Finish the transaction, returning all of its changes as one diff.
+    pub fn commit(mut self) -> GraphDiff<V, E, VP, EP> {
+        GraphDiff::Group(core::mem::take(&mut self.diffs))
+    }
+
+    /// Roll back every change made in this transaction, panicking if that
+    /// fails. See [`Transaction::rollback`].
+    pub fn abort(self) {
+        self.rollback()
+            .expect("Graph state has become corrupted while aborting a transaction");
+    }
+
+    /// Roll back every change made in this transaction. If that fails, the
+    /// changes are all left in place.
+    pub fn rollback(mut self) -> Result<(), GraphError> {
+        self.rollback_all()
+    }
+
+    fn rollback_all(&mut self) -> Result<(), GraphError> {
+        // all or nothing, so a failure can't leave the graph half rolled back
+        self.graph.rollback_diffs(core::mem::take(&mut self.diffs))
+    }
+}
+
+impl<'a, V: Clone, E: Clone, VP: Patch<V>, EP: Patch<E>> Record<V, E, VP, EP>
+    for Transaction<'a, V, E, VP, EP>
+{
+    fn recorded_graph(&mut self) -> &mut Graph<V, E> {
+        self.graph
+    }
+
+    fn record(&mut self, diff: GraphDiff<V, E, VP, EP>) {
+        self.diffs.push(diff);
+    }
+}
+
+impl<'a, V: Clone, E: Clone, VP: Patch<V>, EP: Patch<E>> Drop for Transaction<'a, V, E, VP, EP> {
+    fn drop(&mut self) {
+        // not committed, so leave the graph as we found it. Panicking here
+        // would abort the process if we're already unwinding, so a graph that
+        // was corrupted is left as it is.
+        let _ = self.rollback_all();
+    }
+}
+
+impl<V: Clone, E: Clone> Graph<V, E> {
+    /// Start a transaction. See [`Transaction`].
+    pub fn begin_transaction(&mut self) -> Transaction<'_, V, E> {
+        Transaction::new(self)
+    }
+
+    /// Run `f` inside a transaction. If `f` succeeds, the transaction is
+    /// committed and its diff is returned alongside `f`'s result. If `f`
+    /// returns an error, all of its changes are rolled back.
+    pub fn transaction<T, F>(&mut self, f: F) -> Result<(T, GraphDiff<V, E>), GraphError>
+    where
+        F: FnOnce(&mut Transaction<'_, V, E>) -> Result<T, GraphError>,
+    {
+        self.patched_transaction(f)
+    }
+
+    /// Same as [`Graph::transaction`], for transactions that can also patch
+    /// data (see [`crate::Patchable`]). `VP` and `EP` are the patch types of the
+    /// returned diff.
+    #[allow(clippy::type_complexity)]
+    pub fn patched_transaction<VP, EP, T, F>(
+        &mut self,
+        f: F,
+    ) -> Result<(T, GraphDiff<V, E, VP, EP>), GraphError>
+    where
+        VP: Patch<V>,
+        EP: Patch<E>,
+        F: FnOnce(&mut Transaction<'_, V, E, VP, EP>) -> Result<T, GraphError>,
+    {
+        let mut transaction = Transaction::new(self);
+
+        match f(&mut transaction) {
+            Ok(value) => Ok((value, transaction.commit())),
+            Err(err) => {
+                transaction.abort();
+
+                Err(err)
+            }
+        }
+    }
+}
@@ -0,0 +1,339 @@
+use alloc::vec::Vec;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::GraphError,
+    gen_vec::{GenVec, Index},
+    graph::Graph,
+    graph_diff::GraphDiff,
+    patch::{NoPatch, Patch},
+    recorder::sealed::Record,
+    transaction::Transaction,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StateIndex(pub(crate) Index);
+
+/// A state in an [`UndoTree`], reached by applying its diff to its parent's
+/// state.
+#[derive(Debug, Clone)]
+pub struct UndoState<V, E, VP = NoPatch, EP = NoPatch> {
+    parent: Option<StateIndex>,
+    children: Vec<StateIndex>,
+    /// The child that redo moves to
+    redo_child: Option<StateIndex>,
+    diff: GraphDiff<V, E, VP, EP>,
+    sequence: usize,
+}
+
+impl<V, E, VP, EP> UndoState<V, E, VP, EP> {
+    /// `None` for the root state.
+    pub fn get_parent(&self) -> Option<StateIndex> {
+        self.parent
+    }
+
+    /// Oldest first.
+    pub fn get_children(&self) -> &[StateIndex] {
+        &self.children
+    }
+
+    /// The diff that leads from the parent state to this one. Empty for the
+    /// root state.
+    pub fn get_diff(&self) -> &GraphDiff<V, E, VP, EP> {
+        &self.diff
+    }
+
+    /// Order in which the states were created, counting from 0 for the root.
+    pub fn get_sequence(&self) -> usize {
+        self.sequence
+    }
+}
+
+/// A graph that records every change in a tree, so making an edit after
+/// undoing starts a new branch instead of throwing the old one away.
+///
+/// Like [`crate::History`], all changes have to go through the tree, using
+/// [`crate::Recorder`]. `VP` and `EP` are the patch types of its diffs, see
+/// [`GraphDiff`].
+#[derive(Debug, Clone)]
+pub struct UndoTree<V, E, VP = NoPatch, EP = NoPatch> {
+    graph: Graph<V, E>,
+    states: GenVec<UndoState<V, E, VP, EP>>,
+    root: StateIndex,
+    current: StateIndex,
+    next_sequence: usize,
+}
+
+impl<V: Clone, E: Clone, VP: Patch<V>, EP: Patch<E>> UndoTree<V, E, VP, EP> {
+    pub fn new() -> UndoTree<V, E, VP, EP> {
+        UndoTree::from_graph(Graph::new())
+    }
+
+    /// Start recording history for an existing graph. The graph's current
+    /// state becomes the root of the tree.
+    pub fn from_graph(graph: Graph<V, E>) -> UndoTree<V, E, VP, EP> {
+        let mut states = GenVec::new();
+        let root = StateIndex(states.add(UndoState {
+            parent: None,
+            children: Vec::new(),
+            redo_child: None,
+            diff: GraphDiff::Group(Vec::new()),
+            sequence: 0,
+        }));
+
+        UndoTree {
+            graph,
+            states,
+            root,
+            current: root,
+            next_sequence: 1,
+        }
+    }
+
+    pub fn graph(&self) -> &Graph<V, E> {
+        &self.graph
+    }
+
+    pub fn into_graph(self) -> Graph<V, E> {
+        self.graph
+    }
+
+    pub fn root(&self) -> StateIndex {
+        self.root
+    }
+
+    /// The state the graph is currently in.
+    pub fn current(&self) -> StateIndex {
+        self.current
+    }
+
+    pub fn get_state(&self, index: StateIndex) -> Option<&UndoState<V, E, VP, EP>> {
+        self.states.get(index.0)
+    }
+
+    /// The tip of every branch (states without children), oldest first.
+    pub fn branches(&self) -> Vec<StateIndex> {
+        let mut leaves: Vec<(usize, StateIndex)> = self
+            .states
+            .iter()
+            .filter(|(_, state)| state.children.is_empty())
+            .map(|(index, state)| (state.sequence, StateIndex(index)))
+            .collect();
+        leaves.sort();
+
+        leaves.into_iter().map(|(_, index)| index).collect()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.states[self.current.0].parent.is_some()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.states[self.current.0].redo_child.is_some()
+    }
+
+    /// Run `f` inside a transaction, recording all of its changes as a single
+    /// state. If `f` returns an error, its changes are rolled back and nothing
+    /// is recorded.
+    pub fn transaction<T, F>(&mut self, f: F) -> Result<T, GraphError>
+    where
+        F: FnOnce(&mut Transaction<'_, V, E, VP, EP>) -> Result<T, GraphError>,
+    {
+        let (value, diff) = self.graph.patched_transaction(f)?;
+
+        if !matches!(&diff, GraphDiff::Group(diffs) if diffs.is_empty()) {
+            self.push(diff);
+        }
+
+        Ok(value)
+    }
+
+    /// Move to the parent state. Returns `NothingToUndo` at the root.
+    pub fn undo(&mut self) -> Result<(), GraphError> {
+        let parent = self.states[self.current.0]
+            .parent
+            .ok_or(GraphError::NothingToUndo)?;
+
+        self.graph
+            .rollback_diff_ref(&self.states[self.current.0].diff)?;
+        self.states[parent.0].redo_child = Some(self.current);
+        self.current = parent;
+
+        Ok(())
+    }
+
+    /// Move to the child state that was most recently created or undone
+    /// from. Returns `NothingToRedo` if there are no children.
+    pub fn redo(&mut self) -> Result<(), GraphError> {
+        let child = self.states[self.current.0]
+            .redo_child
+            .ok_or(GraphError::NothingToRedo)?;
+
+        self.graph.apply_diff_ref(&self.states[child.0].diff)?;
+        self.current = child;
+
+        Ok(())
+    }
+
+    /// Move to any state in the tree, rolling back to the closest common
+    /// ancestor and applying diffs from there. If anything fails along the
+    /// way, the graph is left in the current state.
+    pub fn jump_to(&mut self, target: StateIndex) -> Result<(), GraphError> {
+        if self.states.get(target.0).is_none() {
+            return Err(GraphError::StateDoesNotExist { index: target });
+        }
+
+        let current_path = self.path_from_root(self.current);
+        let target_path = self.path_from_root(target);
+
+        let shared = current_path
+            .iter()
+            .zip(target_path.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        // (state, whether it's being applied)
+        let steps: Vec<(StateIndex, bool)> = current_path[shared..]
+            .iter()
+            .rev()
+            .map(|state| (*state, false))
+            .chain(target_path[shared..].iter().map(|state| (*state, true)))
+            .collect();
+
+        for (position, (state, forward)) in steps.iter().enumerate() {
+            if let Err(err) = self.step(*state, *forward) {
+                for (state, forward) in steps[..position].iter().rev() {
+                    self.step(*state, !forward)
+                        .expect("Undo tree has become corrupted while jumping between states");
+                }
+
+                return Err(err);
+            }
+        }
+
+        // redo should retrace the path that was just taken
+        for state in &target_path[shared..] {
+            let parent = self.states[state.0].parent.unwrap();
+            self.states[parent.0].redo_child = Some(*state);
+        }
+
+        self.current = target;
+
+        Ok(())
+    }
+
+    /// Remove a state and everything that branches off of it. The root, the
+    /// current state and its ancestors can't be pruned.
+    pub fn prune(&mut self, index: StateIndex) -> Result<(), GraphError> {
+        let parent = self
+            .states
+            .get(index.0)
+            .ok_or(GraphError::StateDoesNotExist { index })?
+            .parent;
+
+        if self.path_from_root(self.current).contains(&index) {
+            return Err(GraphError::StateInUse { index });
+        }
+
+        // only the root has no parent, and it's always in use
+        let parent = &mut self.states[parent.unwrap().0];
+        parent.children.retain(|child| *child != index);
+        if parent.redo_child == Some(index) {
+            parent.redo_child = parent.children.last().copied();
+        }
+
+        let mut to_remove = Vec::from([index]);
+        while let Some(index) = to_remove.pop() {
+            let state = self.states.remove(index.0).unwrap();
+            to_remove.extend(state.children);
+        }
+
+        Ok(())
+    }
+
+    /// Prune every branch except the path from the root to the current state,
+    /// and the states that redo would move through from there.
+    pub fn prune_inactive(&mut self) {
+        let mut keep = self.path_from_root(self.current);
+
+        let mut redo_child = self.states[self.current.0].redo_child;
+        while let Some(child) = redo_child {
+            keep.push(child);
+            redo_child = self.states[child.0].redo_child;
+        }
+
+        for state in keep.iter() {
+            let inactive: Vec<StateIndex> = self.states[state.0]
+                .children
+                .iter()
+                .filter(|child| !keep.contains(child))
+                .copied()
+                .collect();
+
+            for child in inactive {
+                self.prune(child)
+                    .expect("Undo tree has become corrupted while pruning");
+            }
+        }
+    }
+
+    fn step(&mut self, state: StateIndex, forward: bool) -> Result<(), GraphError> {
+        let diff = &self.states[state.0].diff;
+
+        if forward {
+            self.graph.apply_diff_ref(diff)
+        } else {
+            self.graph.rollback_diff_ref(diff)
+        }
+    }
+
+    /// Every state from the root down to (and including) `index`.
+    fn path_from_root(&self, index: StateIndex) -> Vec<StateIndex> {
+        let mut path = Vec::from([index]);
+
+        while let Some(parent) = self.states[path.last().unwrap().0].parent {
+            path.push(parent);
+        }
+        path.reverse();
+
+        path
+    }
+
+    fn push(&mut self, diff: GraphDiff<V, E, VP, EP>) {
+        let state = StateIndex(self.states.add(UndoState {
+            parent: Some(self.current),
+            children: Vec::new(),
+            redo_child: None,
+            diff,
+            sequence: self.next_sequence,
+        }));
+        self.next_sequence += 1;
+
+        let current = &mut self.states[self.current.0];
+        current.children.push(state);
+        current.redo_child = Some(state);
+
+        self.current = state;
+    }
+}
+
+impl<V: Clone, E: Clone, VP: Patch<V>, EP: Patch<E>> Record<V, E, VP, EP>
+    for UndoTree<V, E, VP, EP>
+{
+    fn recorded_graph(&mut self) -> &mut Graph<V, E> {
+        &mut self.graph
+    }
+
+    fn record(&mut self, diff: GraphDiff<V, E, VP, EP>) {
+        self.push(diff);
+    }
+}
+
+impl<V: Clone, E: Clone, VP: Patch<V>, EP: Patch<E>> Default for UndoTree<V, E, VP, EP> {
+    fn default() -> Self {
+        UndoTree::new()
+    }
+}
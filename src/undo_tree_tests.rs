@@ -0,0 +1,158 @@
+use crate::{GraphDiff, GraphError, Patch, Patchable, Recorder, UndoTree};
+
+#[test]
+fn test_undo_tree_keeps_branches() {
+    let mut tree: UndoTree<i32, ()> = UndoTree::new();
+
+    let first = tree.add_vertex(1);
+    let before_branch = tree.current();
+    tree.update_vertex(first, 2).unwrap();
+    let old_branch = tree.current();
+
+    // editing after an undo starts a new branch
+    tree.undo().unwrap();
+    tree.update_vertex(first, 3).unwrap();
+    let new_branch = tree.current();
+
+    assert_eq!(tree.branches(), vec![old_branch, new_branch]);
+    assert_eq!(
+        tree.get_state(before_branch).unwrap().get_children(),
+        &[old_branch, new_branch]
+    );
+
+    tree.jump_to(old_branch).unwrap();
+    assert_eq!(*tree.graph().get_vertex_data(first).unwrap(), 2);
+
+    tree.jump_to(tree.root()).unwrap();
+    assert!(tree.graph().get_vertex(first).is_none());
+    assert!(matches!(tree.undo(), Err(GraphError::NothingToUndo)));
+
+    // redo follows the path most recently taken
+    tree.redo().unwrap();
+    tree.redo().unwrap();
+    assert_eq!(tree.current(), old_branch);
+    assert!(matches!(tree.redo(), Err(GraphError::NothingToRedo)));
+
+    tree.jump_to(new_branch).unwrap();
+    assert_eq!(*tree.graph().get_vertex_data(first).unwrap(), 3);
+    tree.undo().unwrap();
+    tree.redo().unwrap();
+    assert_eq!(tree.current(), new_branch);
+}
+
+#[test]
+fn test_undo_tree_jump_between_branches() {
+    let mut tree: UndoTree<i32, i32> = UndoTree::new();
+
+    let first = tree.add_vertex(1);
+    let second = tree.add_vertex(2);
+    let fork = tree.current();
+
+    let edge = tree.add_edge(first, second, 10).unwrap();
+    tree.update_edge(edge, 20).unwrap();
+    let with_edge = tree.current();
+
+    tree.jump_to(fork).unwrap();
+    tree.remove_vertex(first).unwrap();
+    let third = tree.add_vertex(3);
+    let without_first = tree.current();
+
+    tree.jump_to(with_edge).unwrap();
+    assert_eq!(*tree.graph().get_edge_data(edge).unwrap(), 20);
+    assert!(tree.graph().get_vertex(third).is_none());
+
+    tree.jump_to(without_first).unwrap();
+    assert!(tree.graph().get_vertex(first).is_none());
+    assert!(tree.graph().get_edge(edge).is_none());
+    assert_eq!(*tree.graph().get_vertex_data(third).unwrap(), 3);
+    assert_eq!(tree.graph().validate(), vec![]);
+}
+
+#[test]
+fn test_undo_tree_prune() {
+    let mut tree: UndoTree<i32, ()> = UndoTree::new();
+
+    let first = tree.add_vertex(1);
+    tree.update_vertex(first, 2).unwrap();
+    let old_branch = tree.current();
+    tree.undo().unwrap();
+    tree.update_vertex(first, 3).unwrap();
+    tree.update_vertex(first, 4).unwrap();
+
+    assert!(matches!(
+        tree.prune(tree.root()),
+        Err(GraphError::StateInUse { .. })
+    ));
+    assert!(matches!(
+        tree.prune(tree.current()),
+        Err(GraphError::StateInUse { .. })
+    ));
+
+    tree.prune(old_branch).unwrap();
+    assert_eq!(tree.branches(), vec![tree.current()]);
+    assert!(tree.get_state(old_branch).is_none());
+    assert!(matches!(
+        tree.jump_to(old_branch),
+        Err(GraphError::StateDoesNotExist { .. })
+    ));
+
+    // only keeps what redo would move through
+    tree.undo().unwrap();
+    tree.undo().unwrap();
+    tree.update_vertex(first, 5).unwrap();
+    let newest = tree.current();
+    tree.undo().unwrap();
+
+    tree.prune_inactive();
+    assert_eq!(tree.branches(), vec![newest]);
+    tree.redo().unwrap();
+    assert_eq!(*tree.graph().get_vertex_data(first).unwrap(), 5);
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Total(i32);
+
+#[derive(Debug, Clone, PartialEq)]
+struct Delta(i32);
+
+impl Patch<Total> for Delta {
+    fn apply(&self, data: &mut Total) {
+        data.0 += self.0;
+    }
+
+    fn revert(&self, data: &mut Total) {
+        data.0 -= self.0;
+    }
+}
+
+impl Patchable for Total {
+    type Patch = Delta;
+
+    fn make_patch(&self, after: &Self) -> Self::Patch {
+        Delta(after.0 - self.0)
+    }
+}
+
+#[test]
+fn test_undo_tree_patch() {
+    let mut tree: UndoTree<Total, (), Delta> = UndoTree::new();
+
+    let vertex = tree.add_vertex(Total(1));
+    tree.patch_vertex(vertex, Delta(10)).unwrap();
+    let old_branch = tree.current();
+    assert!(matches!(
+        tree.get_state(old_branch).unwrap().get_diff(),
+        GraphDiff::PatchVertexData(_)
+    ));
+
+    tree.undo().unwrap();
+    tree.patch_vertex_with(vertex, |total| total.0 *= 5)
+        .unwrap();
+    assert_eq!(*tree.graph().get_vertex_data(vertex).unwrap(), Total(5));
+
+    tree.jump_to(old_branch).unwrap();
+    assert_eq!(*tree.graph().get_vertex_data(vertex).unwrap(), Total(11));
+
+    tree.undo().unwrap();
+    assert_eq!(*tree.graph().get_vertex_data(vertex).unwrap(), Total(1));
+}
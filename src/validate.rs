@@ -0,0 +1,278 @@
+use alloc::vec::Vec;
+
+use crate::{
+    gen_vec::{Element, GenVec},
+    graph::{Edge, EdgeIndex, Graph, VertexIndex},
+};
+
+/// Which of the graph's two slot lists something refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotKind {
+    Vertex,
+    Edge,
+}
+
+/// Which connection list of a vertex something refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionList {
+    /// [`crate::Vertex::get_connections_from`]
+    From,
+    /// [`crate::Vertex::get_connections_to`]
+    To,
+}
+
+/// A broken invariant found by [`Graph::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inconsistency {
+    /// The edge points at a vertex slot that is open (or doesn't exist).
+    DanglingEndpoint {
+        edge: EdgeIndex,
+        vertex: VertexIndex,
+    },
+    /// The edge points at a vertex slot that is occupied by a different
+    /// generation.
+    EndpointGenerationMismatch {
+        edge: EdgeIndex,
+        vertex: VertexIndex,
+        actual: u32,
+    },
+    /// The edge is missing from one of its endpoints' connection lists.
+    MissingConnection {
+        vertex: VertexIndex,
+        edge: EdgeIndex,
+        list: ConnectionList,
+    },
+    /// The edge is listed more than once in one of its endpoints' connection
+    /// lists.
+    DuplicateConnection {
+        vertex: VertexIndex,
+        edge: EdgeIndex,
+        list: ConnectionList,
+    },
+    /// A vertex lists a connection that doesn't match any edge.
+    UnknownConnection {
+        vertex: VertexIndex,
+        edge: EdgeIndex,
+        list: ConnectionList,
+    },
+    /// A vertex lists a connection to an edge slot that is occupied by a
+    /// different generation.
+    ConnectionGenerationMismatch {
+        vertex: VertexIndex,
+        edge: EdgeIndex,
+        actual: u32,
+    },
+    /// Following the free list never ends.
+    FreeListCycle { kind: SlotKind },
+    /// The free list points at an occupied slot.
+    FreeListOccupied { kind: SlotKind, slot: usize },
+    /// The free list points past the end of the slots.
+    FreeListOutOfBounds { kind: SlotKind, slot: usize },
+    /// The free list's back link of this slot doesn't point at the slot before
+    /// it.
+    FreeListBrokenLink { kind: SlotKind, slot: usize },
+    /// An open slot can't be reached through the free list, so it will never
+    /// be reused.
+    OpenSlotNotInFreeList { kind: SlotKind, slot: usize },
+}
+
+impl<V, E> Graph<V, E> {
+    /// Check every invariant of the graph, returning a list of everything
+    /// that's wrong. An empty list means the graph is consistent.
+    pub fn validate(&self) -> Vec<Inconsistency> {
+        let mut problems = Vec::new();
+
+        validate_free_list(&self.verticies, SlotKind::Vertex, &mut problems);
+        validate_free_list(&self.edges, SlotKind::Edge, &mut problems);
+
+        // every edge should be listed exactly once on each side
+        for (index, edge) in self.edges.iter() {
+            let edge_index = EdgeIndex(index);
+
+            for (vertex_index, list) in [
+                (edge.from, ConnectionList::To),
+                (edge.to, ConnectionList::From),
+            ] {
+                let vertex = match self.verticies.vec.get(vertex_index.0.index) {
+                    Some(Element::Occupied { value, generation })
+                        if *generation == vertex_index.0.generation =>
+                    {
+                        value
+                    }
+                    Some(Element::Occupied { generation, .. }) => {
+                        problems.push(Inconsistency::EndpointGenerationMismatch {
+                            edge: edge_index,
+                            vertex: vertex_index,
+                            actual: *generation,
+                        });
+                        continue;
+                    }
+                    _ => {
+                        problems.push(Inconsistency::DanglingEndpoint {
+                            edge: edge_index,
+                            vertex: vertex_index,
+                        });
+                        continue;
+                    }
+                };
+
+                let (connections, other) = match list {
+                    ConnectionList::To => (&vertex.connections_to, edge.to),
+                    ConnectionList::From => (&vertex.connections_from, edge.from),
+                };
+
+                let count = connections
+                    .iter()
+                    .filter(|connection| **connection == (other, edge_index))
+                    .count();
+
+                if count == 0 {
+                    problems.push(Inconsistency::MissingConnection {
+                        vertex: vertex_index,
+                        edge: edge_index,
+                        list,
+                    });
+                } else if count > 1 {
+                    problems.push(Inconsistency::DuplicateConnection {
+                        vertex: vertex_index,
+                        edge: edge_index,
+                        list,
+                    });
+                }
+            }
+        }
+
+        // and every connection should belong to an edge
+        for (index, vertex) in self.verticies.iter() {
+            let vertex_index = VertexIndex(index);
+
+            let lists = [
+                (&vertex.connections_to, ConnectionList::To),
+                (&vertex.connections_from, ConnectionList::From),
+            ];
+
+            for (connections, list) in lists {
+                for (other, edge_index) in connections.iter() {
+                    let matches = |edge: &Edge<E>| match list {
+                        ConnectionList::To => edge.from == vertex_index && edge.to == *other,
+                        ConnectionList::From => edge.to == vertex_index && edge.from == *other,
+                    };
+
+                    match self.edges.vec.get(edge_index.0.index) {
+                        Some(Element::Occupied { value, generation })
+                            if *generation == edge_index.0.generation =>
+                        {
+                            if !matches(value) {
+                                problems.push(Inconsistency::UnknownConnection {
+                                    vertex: vertex_index,
+                                    edge: *edge_index,
+                                    list,
+                                });
+                            }
+                        }
+                        Some(Element::Occupied { generation, .. }) => {
+                            problems.push(Inconsistency::ConnectionGenerationMismatch {
+                                vertex: vertex_index,
+                                edge: *edge_index,
+                                actual: *generation,
+                            });
+                        }
+                        _ => {
+                            problems.push(Inconsistency::UnknownConnection {
+                                vertex: vertex_index,
+                                edge: *edge_index,
+                                list,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        problems
+    }
+
+    /// Rebuild every vertex's connection lists from the edges, and both free
+    /// lists from the open slots. Edges whose endpoints don't exist can't be
+    /// connected to anything, so they're removed and returned.
+    ///
+    /// After this, [`Graph::validate`] will find no problems.
+    pub fn repair(&mut self) -> Vec<(EdgeIndex, Edge<E>)> {
+        self.verticies.rebuild_free_list();
+        self.edges.rebuild_free_list();
+
+        let dangling: Vec<EdgeIndex> = self
+            .edges
+            .iter()
+            .filter(|(_, edge)| {
+                self.verticies.get(edge.from.0).is_none() || self.verticies.get(edge.to.0).is_none()
+            })
+            .map(|(index, _)| EdgeIndex(index))
+            .collect();
+
+        let removed = dangling
+            .into_iter()
+            .map(|index| (index, self.edges.remove(index.0).unwrap()))
+            .collect();
+
+        self.rebuild_connections();
+
+        removed
+    }
+
+    /// Expects every edge's endpoints to exist.
+    pub(crate) fn rebuild_connections(&mut self) {
+        for (_, vertex) in self.verticies.iter_mut() {
+            vertex.connections_from.clear();
+            vertex.connections_to.clear();
+        }
+
+        for (index, edge) in self.edges.iter() {
+            let edge_index = EdgeIndex(index);
+
+            self.verticies[edge.from.0].add_to_unchecked(edge.to, edge_index);
+            self.verticies[edge.to.0].add_from_unchecked(edge.from, edge_index);
+        }
+    }
+}
+
+fn validate_free_list<T>(gen_vec: &GenVec<T>, kind: SlotKind, problems: &mut Vec<Inconsistency>) {
+    let mut in_free_list = Vec::new();
+    in_free_list.resize(gen_vec.vec.len(), false);
+
+    let mut prev_slot = None;
+    let mut current = gen_vec.next_open_slot;
+
+    while let Some(slot) = current {
+        match gen_vec.vec.get(slot) {
+            Some(Element::Open { next, prev, .. }) => {
+                if in_free_list[slot] {
+                    problems.push(Inconsistency::FreeListCycle { kind });
+                    break;
+                }
+
+                if *prev != prev_slot {
+                    problems.push(Inconsistency::FreeListBrokenLink { kind, slot });
+                }
+
+                in_free_list[slot] = true;
+                prev_slot = Some(slot);
+                current = *next;
+            }
+            Some(Element::Occupied { .. }) => {
+                problems.push(Inconsistency::FreeListOccupied { kind, slot });
+                break;
+            }
+            None => {
+                problems.push(Inconsistency::FreeListOutOfBounds { kind, slot });
+                break;
+            }
+        }
+    }
+
+    for (slot, element) in gen_vec.vec.iter().enumerate() {
+        if matches!(element, Element::Open { .. }) && !in_free_list[slot] {
+            problems.push(Inconsistency::OpenSlotNotInFreeList { kind, slot });
+        }
+    }
+}